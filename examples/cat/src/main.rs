@@ -1,12 +1,11 @@
 use std::io::{stdin, stdout};
 
-use braincluck_interpreter::{bf_parse, Cells};
+use braincluck_interpreter::{EofBehaviour, Interpreter};
 
 fn main() {
-    let mut cells = Cells::default();
     let program = ",[.,]";
-    let commands = bf_parse(program).unwrap();
-    let mut out = stdout();
-    let mut input = stdin();
-    cells.interpret(&commands, &mut out, &mut input).unwrap();
+    Interpreter::default()
+        .with_eof_behaviour(EofBehaviour::Zero)
+        .run(program, &mut stdout(), &mut stdin())
+        .unwrap();
 }
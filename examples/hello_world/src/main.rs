@@ -1,12 +1,9 @@
 use std::io::{stdin, stdout};
 
-use braincluck_interpreter::{bf_parse, Cells};
+use braincluck_interpreter::Cells;
+use braincluck_macros::bf;
 
 fn main() {
-    let mut cells = Cells::default();
-    let program = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
-    let commands = bf_parse(program).unwrap();
-    let mut out = stdout();
-    let mut input = stdin();
-    cells.interpret(&commands, &mut out, &mut input).unwrap();
+    let hello_world = bf!("++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.");
+    hello_world(&mut Cells::<u8>::default(), &mut stdout(), &mut stdin()).unwrap();
 }
@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use braincluck_interpreter::{codegen, ir::Program};
+use clap::Args;
+
+use crate::{
+    options::{load_program, ConfigArgs},
+    EXIT_IO_ERROR, EXIT_UNSUPPORTED,
+};
+
+#[derive(Args, Debug)]
+pub struct BuildArgs {
+    /// The file with the program to build. If this is `-` or missing, the program is read from stdin instead.
+    file: Option<PathBuf>,
+
+    /// Where to write the executable. Defaults to the program's file name without its extension, `<file>.out` for
+    /// programs without an extension, or `a.out` for programs read from stdin.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+pub fn build(args: BuildArgs) -> ExitCode {
+    let (_, commands) = match load_program(args.file.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+
+    let config = args.config.config();
+    let elf = match codegen::elf::generate(&Program::new(&commands), &config) {
+        Ok(elf) => elf,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(EXIT_UNSUPPORTED);
+        }
+    };
+
+    let output = args.output.unwrap_or_else(|| {
+        match args.file.as_deref().filter(|file| file.as_os_str() != "-") {
+            Some(file) if file.extension().is_some() => file.with_extension(""),
+            Some(file) => file.with_extension("out"),
+            None => PathBuf::from("a.out"),
+        }
+    });
+    if args
+        .file
+        .as_deref()
+        .is_some_and(|file| same_file(file, &output))
+    {
+        eprintln!(
+            "error: not writing the executable to {}, as that would overwrite the program",
+            output.display()
+        );
+        return ExitCode::from(EXIT_IO_ERROR);
+    }
+    match write_executable(&output, &elf) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: couldn't write {}: {err}", output.display());
+            ExitCode::from(EXIT_IO_ERROR)
+        }
+    }
+}
+
+/// Returns whether `a` and `b` are the same existing file.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn write_executable(path: &Path, elf: &[u8]) -> std::io::Result<()> {
+    fs::write(path, elf)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use braincluck_interpreter::{
+    codegen::{self, asm::Syntax},
+    ir::Program,
+};
+use clap::{Args, ValueEnum};
+
+use crate::{
+    options::{load_program, ConfigArgs},
+    EXIT_IO_ERROR, EXIT_UNSUPPORTED,
+};
+
+#[derive(Args, Debug)]
+pub struct CompileArgs {
+    /// The file with the program to compile. If this is `-` or missing, the program is read from stdin instead.
+    file: Option<PathBuf>,
+
+    /// The language to compile to.
+    #[arg(long, short, value_enum)]
+    target: Target,
+
+    /// Where to write the compiled program. Writes to stdout if this is missing.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// C99 source.
+    C,
+    /// A WebAssembly module.
+    Wasm,
+    /// An x86-64 Linux assembly listing for the GNU assembler.
+    Asm,
+    /// An x86-64 Linux assembly listing for NASM.
+    Nasm,
+    /// A JavaScript function.
+    Js,
+}
+
+pub fn compile(args: CompileArgs) -> ExitCode {
+    let (source, commands) = match load_program(args.file.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+
+    let program = Program::new(&commands);
+    let config = args.config.config();
+    let compiled =
+        match args.target {
+            Target::C => codegen::c::generate(&program, &config).map(String::into_bytes),
+            Target::Wasm => codegen::wasm::generate(&program, &config),
+            Target::Asm => codegen::asm::generate(&program, &config, &source, Syntax::Gas)
+                .map(String::into_bytes),
+            Target::Nasm => codegen::asm::generate(&program, &config, &source, Syntax::Nasm)
+                .map(String::into_bytes),
+            Target::Js => codegen::js::generate(&program, &config).map(String::into_bytes),
+        };
+    let compiled = match compiled {
+        Ok(compiled) => compiled,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(EXIT_UNSUPPORTED);
+        }
+    };
+
+    let written = match &args.output {
+        Some(path) => fs::write(path, compiled),
+        None => io::stdout().lock().write_all(&compiled),
+    };
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: couldn't write the compiled program: {err}");
+            ExitCode::from(EXIT_IO_ERROR)
+        }
+    }
+}
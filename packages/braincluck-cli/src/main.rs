@@ -0,0 +1,97 @@
+use std::process::ExitCode;
+
+use braincluck_interpreter::BraincluckError;
+use clap::{Parser, Subcommand};
+
+mod build;
+mod compile;
+mod options;
+mod repl;
+mod run;
+
+/// The program ran, but stopped with an error such as reading past the end of the input.
+const EXIT_RUNTIME_ERROR: u8 = 1;
+/// The program file couldn't be read.
+const EXIT_IO_ERROR: u8 = 3;
+/// The program has unmatched brackets.
+const EXIT_PARSE_ERROR: u8 = 4;
+/// The program ran out of steps.
+const EXIT_STEP_LIMIT: u8 = 5;
+/// The program can't be compiled with the given settings.
+const EXIT_UNSUPPORTED: u8 = 6;
+
+/// Runs Brainfuck programs.
+#[derive(Parser, Debug)]
+#[command(name = "braincluck", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Runs a program against stdin and stdout.
+    Run(run::RunArgs),
+    /// Starts an interactive session, where each line is run on the same tape.
+    Repl(repl::ReplArgs),
+    /// Compiles a program to another language.
+    Compile(compile::CompileArgs),
+    /// Builds a program into a static x86-64 Linux executable, without needing a C compiler.
+    Build(build::BuildArgs),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Run(args) => run::run(args),
+        Command::Compile(args) => compile::compile(args),
+        Command::Build(args) => build::build(args),
+        Command::Repl(args) => match repl::repl(args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {err}");
+                ExitCode::from(EXIT_IO_ERROR)
+            }
+        },
+    }
+}
+
+/// Returns the exit code for a program that stopped with `err`.
+fn exit_code(err: &BraincluckError) -> u8 {
+    match err.inner() {
+        BraincluckError::ParseError(_) => EXIT_PARSE_ERROR,
+        BraincluckError::StepLimitExceeded { .. } => EXIT_STEP_LIMIT,
+        _ => EXIT_RUNTIME_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use braincluck_interpreter::{BraincluckError, Interpreter, TapeMode};
+
+    use super::{exit_code, EXIT_PARSE_ERROR, EXIT_RUNTIME_ERROR, EXIT_STEP_LIMIT};
+
+    #[test]
+    fn exit_codes() {
+        for (interpreter, program, expected) in [
+            (Interpreter::default(), "[", EXIT_PARSE_ERROR),
+            (Interpreter::default(), ",", EXIT_RUNTIME_ERROR),
+            (
+                Interpreter::default().with_tape_mode(TapeMode::Bounded(1)),
+                ">",
+                EXIT_RUNTIME_ERROR,
+            ),
+            (
+                Interpreter::default().with_step_limit(10),
+                "+[]",
+                EXIT_STEP_LIMIT,
+            ),
+        ] {
+            let err: BraincluckError = interpreter
+                .run_to_vec(program, &[])
+                .expect_err("run should fail");
+            assert_eq!(exit_code(&err), expected, "{program}");
+        }
+    }
+}
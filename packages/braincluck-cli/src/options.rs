@@ -0,0 +1,244 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+    process::ExitCode,
+};
+
+use braincluck_interpreter::{
+    bf_parse_recovering, ArithmeticMode, CellWidth, Commands, Config, EofBehaviour, OutputMode,
+    TapeMode,
+};
+use clap::{builder::RangedU64ValueParser, Args, ValueEnum};
+
+use crate::{EXIT_IO_ERROR, EXIT_PARSE_ERROR};
+
+/// The interpreter settings shared by every subcommand that runs a program.
+#[derive(Args, Debug, Clone)]
+pub struct ConfigArgs {
+    /// The type of each memory cell.
+    #[arg(long, value_enum, default_value_t = CellSize::U8)]
+    pub cell_size: CellSize,
+
+    /// What `,` does once the input runs out.
+    #[arg(long, value_enum, default_value_t = Eof::Error)]
+    pub eof: Eof,
+
+    /// Limits the tape to this many cells, rather than growing it as needed.
+    #[arg(
+        long,
+        value_name = "CELLS",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub tape_length: Option<usize>,
+
+    /// Wraps the pointer around the ends of the tape instead of stopping. Needs `--tape-length`.
+    #[arg(long, requires = "tape_length")]
+    pub circular: bool,
+
+    /// Stops the program after running this many commands.
+    #[arg(long, value_name = "STEPS")]
+    pub step_limit: Option<u64>,
+
+    /// Stops the program if a cell goes past its minimum or maximum value, rather than wrapping around.
+    #[arg(long)]
+    pub checked: bool,
+
+    /// Writes each cell as a UTF-8 character, rather than as a single byte.
+    #[arg(long)]
+    pub text: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellSize {
+    I8,
+    U8,
+    U16,
+    U32,
+    I64,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eof {
+    /// Sets the cell to 0.
+    Zero,
+    /// Sets the cell to -1.
+    NegativeOne,
+    /// Leaves the cell as it was.
+    Unchanged,
+    /// Stops the program with an error.
+    Error,
+}
+
+impl From<Eof> for EofBehaviour {
+    fn from(eof: Eof) -> Self {
+        match eof {
+            Eof::Zero => EofBehaviour::Zero,
+            Eof::NegativeOne => EofBehaviour::NegativeOne,
+            Eof::Unchanged => EofBehaviour::Unchanged,
+            Eof::Error => EofBehaviour::Error,
+        }
+    }
+}
+
+impl From<EofBehaviour> for Eof {
+    fn from(eof_behaviour: EofBehaviour) -> Self {
+        match eof_behaviour {
+            EofBehaviour::Zero => Eof::Zero,
+            EofBehaviour::NegativeOne => Eof::NegativeOne,
+            EofBehaviour::Unchanged => Eof::Unchanged,
+            EofBehaviour::Error => Eof::Error,
+        }
+    }
+}
+
+impl ConfigArgs {
+    pub fn config(&self) -> Config {
+        Config {
+            cell_width: match self.cell_size {
+                CellSize::I8 => CellWidth::I8,
+                CellSize::U8 => CellWidth::U8,
+                CellSize::U16 => CellWidth::U16,
+                CellSize::U32 => CellWidth::U32,
+                CellSize::I64 => CellWidth::I64,
+            },
+            tape_mode: match self.tape_length {
+                None => TapeMode::Growable,
+                Some(length) if self.circular => TapeMode::Circular(length),
+                Some(length) => TapeMode::Bounded(length),
+            },
+            eof_behaviour: self.eof.into(),
+            arithmetic_mode: if self.checked {
+                ArithmeticMode::Checked
+            } else {
+                ArithmeticMode::Wrapping
+            },
+            output_mode: if self.text {
+                OutputMode::Text
+            } else {
+                OutputMode::Byte
+            },
+            step_limit: self.step_limit,
+        }
+    }
+}
+
+/// Reads a program from `path`, or from stdin if the path is `-` or missing.
+pub fn read_program(path: Option<&Path>) -> io::Result<String> {
+    match path {
+        Some(path) if path != Path::new("-") => fs::read_to_string(path),
+        _ => {
+            let mut program = String::new();
+            io::stdin().read_to_string(&mut program)?;
+            Ok(program)
+        }
+    }
+}
+
+/// Reads and parses a program, printing any problems with it. If it can't be read or parsed, this returns the
+/// exit code to stop with.
+pub fn load_program(path: Option<&Path>) -> Result<(String, Commands), ExitCode> {
+    let program = match read_program(path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("error: couldn't read the program: {err}");
+            return Err(ExitCode::from(EXIT_IO_ERROR));
+        }
+    };
+
+    let (commands, errors) = bf_parse_recovering(&program);
+    if !errors.is_empty() {
+        for err in errors {
+            eprintln!("{}\n", err.render(&program));
+        }
+        return Err(ExitCode::from(EXIT_PARSE_ERROR));
+    }
+
+    Ok((program, commands))
+}
+
+#[cfg(test)]
+mod tests {
+    use braincluck_interpreter::{
+        ArithmeticMode, CellWidth, Config, EofBehaviour, Interpreter, OutputMode, TapeMode,
+    };
+    use clap::Parser;
+
+    use super::ConfigArgs;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        config: ConfigArgs,
+    }
+
+    fn config(args: &[&str]) -> Config {
+        Cli::try_parse_from(std::iter::once("braincluck").chain(args.iter().copied()))
+            .expect("arguments should parse")
+            .config
+            .config()
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(config(&[]), Config::default());
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(
+            config(&[
+                "--cell-size",
+                "u16",
+                "--eof",
+                "negative-one",
+                "--tape-length",
+                "100",
+                "--step-limit",
+                "1000",
+                "--checked",
+                "--text",
+            ]),
+            Config {
+                cell_width: CellWidth::U16,
+                tape_mode: TapeMode::Bounded(100),
+                eof_behaviour: EofBehaviour::NegativeOne,
+                arithmetic_mode: ArithmeticMode::Checked,
+                output_mode: OutputMode::Text,
+                step_limit: Some(1000),
+            }
+        );
+        assert_eq!(
+            config(&["--tape-length", "8", "--circular"]).tape_mode,
+            TapeMode::Circular(8)
+        );
+    }
+
+    /// Any tape length that fits in memory's address space should be accepted and run, without allocating
+    /// the whole tape up front.
+    #[test]
+    fn long_tape() {
+        let config = config(&["--tape-length", "99999999999999"]);
+        assert_eq!(config.tape_mode, TapeMode::Bounded(99_999_999_999_999));
+
+        let out = Interpreter::new(config)
+            .run_to_vec("++++++++[>++++++++<-]>+.", &[])
+            .expect("run should succeed");
+        assert_eq!(out, b"A");
+    }
+
+    #[test]
+    fn invalid_flags() {
+        for args in [
+            &["--circular"][..],
+            &["--tape-length", "0"],
+            &["--cell-size", "u64"],
+        ] {
+            assert!(
+                Cli::try_parse_from(std::iter::once("braincluck").chain(args.iter().copied()))
+                    .is_err(),
+                "{args:?} should not parse"
+            );
+        }
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    mem,
+};
+
+use braincluck_interpreter::{
+    bf_parse,
+    vm::{Status, Vm},
+    ArithmeticMode, BraincluckError, CellValue, CellWidth, Cells, Config, OutputMode, SourceMap,
+};
+use clap::{Args, ValueEnum};
+
+use crate::options::{ConfigArgs, Eof};
+
+/// How many cells on each side of the pointer `:tape` shows by default.
+const DEFAULT_RADIUS: usize = 5;
+
+const HELP: &str = "\
+Each line is run as a program on the same tape. Lines starting with `:` are commands:
+
+  :tape [RADIUS]        shows the cells around the pointer
+  :reset                clears the tape
+  :load FILE            runs a program from a file on the tape
+  :set                  shows the current settings
+  :set SETTING VALUE    changes a setting, one of:
+                          eof zero|negative-one|unchanged|error
+                          checked on|off
+                          text on|off
+                          step-limit STEPS|off
+  :help                 shows this message
+  :quit                 exits the REPL
+";
+
+#[derive(Args, Debug)]
+pub struct ReplArgs {
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// Whether the REPL should keep going after a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+pub fn repl(args: ReplArgs) -> io::Result<()> {
+    let config = args.config.config();
+    match config.cell_width {
+        CellWidth::I8 => run_repl::<i8>(&config),
+        CellWidth::U8 => run_repl::<u8>(&config),
+        CellWidth::U16 => run_repl::<u16>(&config),
+        CellWidth::U32 => run_repl::<u32>(&config),
+        CellWidth::I64 => run_repl::<i64>(&config),
+    }
+}
+
+fn run_repl<T: CellValue>(config: &Config) -> io::Result<()> {
+    let mut repl = Repl::<T>::new(config);
+    let mut stdin = io::stdin().lock();
+    let mut out = io::stdout().lock();
+
+    writeln!(out, "braincluck REPL. Type `:help` for a list of commands.")?;
+    loop {
+        write!(out, "bf> ")?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            return Ok(());
+        }
+
+        // The same handle is used for the program's input, so `,` reads from the lines typed after this one.
+        if repl.handle_line(line.trim_end_matches(['\n', '\r']), &mut out, &mut stdin)?
+            == Flow::Quit
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// The state of the REPL, which keeps the same [`Cells`] between lines.
+struct Repl<T: CellValue> {
+    cells: Cells<T>,
+    config: Config,
+}
+
+impl<T: CellValue> Repl<T> {
+    fn new(config: &Config) -> Self {
+        Self {
+            cells: config.cells(),
+            config: *config,
+        }
+    }
+
+    /// Handles a single line, writing anything it prints to `out`.
+    fn handle_line<W: Write, R: BufRead>(
+        &mut self,
+        line: &str,
+        out: &mut W,
+        input: &mut R,
+    ) -> io::Result<Flow> {
+        let mut words = line.split_whitespace();
+        if !line.trim_start().starts_with(':') {
+            self.run(line, out, input)?;
+            return Ok(Flow::Continue);
+        }
+
+        match words.next().unwrap_or_default() {
+            ":tape" => {
+                let radius = match words.next().map(str::parse) {
+                    None => DEFAULT_RADIUS,
+                    Some(Ok(radius)) => radius,
+                    Some(Err(_)) => {
+                        writeln!(out, "error: the radius should be a number")?;
+                        return Ok(Flow::Continue);
+                    }
+                };
+                write!(out, "{}", dump_tape(&self.cells, radius))?;
+            }
+            ":reset" => self.cells = self.config.cells(),
+            ":load" => {
+                let path = line.trim_start()[":load".len()..].trim();
+                match fs::read_to_string(path) {
+                    Ok(program) => self.run(&program, out, input)?,
+                    Err(err) => writeln!(out, "error: couldn't read {path}: {err}")?,
+                }
+            }
+            ":set" => match (words.next(), words.next()) {
+                (None, _) => self.write_settings(out)?,
+                (Some(setting), Some(value)) => {
+                    if let Err(message) = self.set(setting, value) {
+                        writeln!(out, "error: {message}")?;
+                    }
+                }
+                (Some(setting), None) => writeln!(out, "error: missing a value for `{setting}`")?,
+            },
+            ":help" => write!(out, "{HELP}")?,
+            ":quit" | ":q" => return Ok(Flow::Quit),
+            command => writeln!(
+                out,
+                "error: unknown command `{command}`, type `:help` for a list of commands"
+            )?,
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Runs a program on the tape. The step limit applies to each program separately.
+    ///
+    /// Output is written as the program runs, and flushed before every `,`, so a prompt written by the program shows
+    /// up before it waits for input.
+    fn run<W: Write, R: BufRead>(
+        &mut self,
+        program: &str,
+        out: &mut W,
+        input: &mut R,
+    ) -> io::Result<()> {
+        let commands = match bf_parse(program) {
+            Ok(commands) => commands,
+            Err(BraincluckError::ParseError(err)) => {
+                return writeln!(out, "{}", err.render(program));
+            }
+            Err(err) => return writeln!(out, "error: {err}"),
+        };
+
+        self.cells.reset_steps();
+        let mut vm = Vm::new(&commands, mem::take(&mut self.cells));
+        let mut output = LastByte::new(&mut *out);
+        let result = loop {
+            match vm.run_until_input(&mut output) {
+                Ok(Status::AwaitingInput) => {
+                    output.flush()?;
+                    if let Err(err) = vm.step(&mut io::sink(), input) {
+                        break Err(err);
+                    }
+                }
+                Ok(_) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        let last = output.last;
+        self.cells = vm.into_cells();
+        if last.is_some_and(|last| last != b'\n') {
+            writeln!(out)?;
+        }
+
+        if let Err(err) = result {
+            writeln!(
+                out,
+                "error: {}",
+                err.with_source_map(&SourceMap::new(program))
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, setting: &str, value: &str) -> Result<(), String> {
+        match setting {
+            "eof" => {
+                let eof = Eof::from_str(value, true)
+                    .map_err(|_| format!("invalid value `{value}` for `eof`"))?;
+                self.config.eof_behaviour = eof.into();
+                self.cells = std::mem::take(&mut self.cells).with_eof_behaviour(eof.into());
+            }
+            "checked" => {
+                self.config.arithmetic_mode = if parse_switch(setting, value)? {
+                    ArithmeticMode::Checked
+                } else {
+                    ArithmeticMode::Wrapping
+                };
+                self.cells = std::mem::take(&mut self.cells)
+                    .with_arithmetic_mode(self.config.arithmetic_mode);
+            }
+            "text" => {
+                self.config.output_mode = if parse_switch(setting, value)? {
+                    OutputMode::Text
+                } else {
+                    OutputMode::Byte
+                };
+                self.cells =
+                    std::mem::take(&mut self.cells).with_output_mode(self.config.output_mode);
+            }
+            "step-limit" => {
+                self.config.step_limit = match value {
+                    "off" => None,
+                    steps => Some(
+                        steps
+                            .parse()
+                            .map_err(|_| format!("invalid value `{value}` for `step-limit`"))?,
+                    ),
+                };
+                self.cells.set_step_limit(self.config.step_limit);
+            }
+            setting => return Err(format!("unknown setting `{setting}`")),
+        }
+
+        Ok(())
+    }
+
+    fn write_settings<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let eof: Eof = self.config.eof_behaviour.into();
+        writeln!(
+            out,
+            "eof {}",
+            eof.to_possible_value()
+                .expect("no values are skipped")
+                .get_name()
+        )?;
+        writeln!(
+            out,
+            "checked {}",
+            on_off(self.config.arithmetic_mode == ArithmeticMode::Checked)
+        )?;
+        writeln!(
+            out,
+            "text {}",
+            on_off(self.config.output_mode == OutputMode::Text)
+        )?;
+        match self.config.step_limit {
+            Some(steps) => writeln!(out, "step-limit {steps}"),
+            None => writeln!(out, "step-limit off"),
+        }
+    }
+}
+
+fn parse_switch(setting: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("`{setting}` should be `on` or `off`")),
+    }
+}
+
+/// Shows the cells within `radius` of the pointer, with their indices, marking the current cell.
+fn dump_tape<T: CellValue>(cells: &Cells<T>, radius: usize) -> String {
+    let start = cells.index().saturating_sub(radius);
+    let end = (cells.index() + radius + 1).min(cells.cells().len());
+
+    let mut indices = String::new();
+    let mut values = String::new();
+    let mut pointer = String::new();
+    for (index, value) in cells.cells().iter().enumerate().take(end).skip(start) {
+        let marker = if index == cells.index() { "^" } else { "" };
+        let index = index.to_string();
+        let value = value.to_string();
+        let width = index.len().max(value.len()) + 1;
+
+        indices.push_str(&format!("{index:>width$}"));
+        values.push_str(&format!("{value:>width$}"));
+        pointer.push_str(&format!("{marker:>width$}"));
+    }
+
+    format!("{}\n{}\n{}\n", indices, values, pointer.trim_end())
+}
+
+/// Passes everything written straight through to `W`, remembering the last byte.
+struct LastByte<W> {
+    inner: W,
+    last: Option<u8>,
+}
+
+impl<W: Write> LastByte<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, last: None }
+    }
+}
+
+impl<W: Write> Write for LastByte<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(&last) = buf[..written].last() {
+            self.last = Some(last);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        io::{self, BufReader, Cursor, Read, Write},
+        rc::Rc,
+    };
+
+    use braincluck_interpreter::{Cells, Config};
+
+    use super::{dump_tape, Flow, Repl};
+
+    /// Runs each line through a new REPL, returning everything it printed.
+    fn run_lines(lines: &[&str]) -> String {
+        let mut repl: Repl<u8> = Repl::new(&Config::default());
+        let mut out = vec![];
+        for line in lines {
+            repl.handle_line(line, &mut out, &mut Cursor::new(vec![]))
+                .expect("handling a line should succeed");
+        }
+
+        String::from_utf8(out).expect("output should be valid utf8")
+    }
+
+    /// The tape should carry over between lines.
+    #[test]
+    fn persistent_tape() {
+        assert_eq!(
+            run_lines(&["++++++++[>+++++++++<-]", ">.", "+.", ":reset", "."]),
+            "H\nI\n\u{0}\n"
+        );
+    }
+
+    #[test]
+    fn tape() {
+        assert_eq!(
+            run_lines(&[">>>>>>>>>>>>+++>+>++++++++++<", ":tape 1"]),
+            " 12 13 14\n  3  1 10\n     ^\n"
+        );
+    }
+
+    #[test]
+    fn dump() {
+        let mut cells: Cells = Cells::default();
+        cells.input(200);
+
+        assert_eq!(dump_tape(&cells, 5), "   0\n 200\n   ^\n");
+    }
+
+    #[test]
+    fn settings() {
+        assert_eq!(
+            run_lines(&[
+                ":set eof zero",
+                ":set checked on",
+                ":set text on",
+                ":set step-limit 10",
+                ":set",
+                ":set eof maybe",
+                ":set colour on",
+            ]),
+            "eof zero\nchecked on\ntext on\nstep-limit 10\n\
+             error: invalid value `maybe` for `eof`\n\
+             error: unknown setting `colour`\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let output = run_lines(&["-[", ":set checked on", "-", ":nope"]);
+
+        assert!(output.contains("unmatched `[`"), "{output}");
+        assert!(
+            output.contains("error: cell 0 went out of bounds"),
+            "{output}"
+        );
+        assert!(output.contains("unknown command `:nope`"), "{output}");
+    }
+
+    /// The step limit should apply to each line separately.
+    #[test]
+    fn step_limit() {
+        let output = run_lines(&[":set step-limit 3", "+++", "+++", "+[]"]);
+
+        assert!(
+            output.starts_with("error: stopped after 3 steps"),
+            "{output}"
+        );
+        assert_eq!(output.lines().count(), 1);
+    }
+
+    #[test]
+    fn load() {
+        let dir = std::env::temp_dir().join(format!("braincluck-repl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creating a directory should work");
+        let path = dir.join("a.b");
+        std::fs::write(&path, "++++++++[>++++++++<-]>+").expect("writing a file should work");
+
+        let output = run_lines(&[&format!(":load {}", path.display()), "."]);
+        std::fs::remove_dir_all(&dir).expect("removing a directory should work");
+
+        assert_eq!(output, "A\n");
+    }
+
+    /// Holds back everything written until it's flushed, like a buffered terminal.
+    struct Screen {
+        shown: Rc<RefCell<Vec<u8>>>,
+        buffer: Vec<u8>,
+    }
+
+    impl Write for Screen {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.buffer.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.shown.borrow_mut().append(&mut self.buffer);
+            Ok(())
+        }
+    }
+
+    /// Remembers what was on the [`Screen`] when input was first read.
+    struct Keyboard {
+        shown: Rc<RefCell<Vec<u8>>>,
+        seen: Option<Vec<u8>>,
+        input: Cursor<Vec<u8>>,
+    }
+
+    impl Read for Keyboard {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.seen.get_or_insert_with(|| self.shown.borrow().clone());
+            self.input.read(buf)
+        }
+    }
+
+    /// A prompt written before a `,` should be shown before the input is read.
+    #[test]
+    fn prompt() {
+        let shown = Rc::new(RefCell::new(vec![]));
+        let mut screen = Screen {
+            shown: shown.clone(),
+            buffer: vec![],
+        };
+        let mut keyboard = BufReader::new(Keyboard {
+            shown,
+            seen: None,
+            input: Cursor::new(b"B".to_vec()),
+        });
+
+        let mut repl: Repl<u8> = Repl::new(&Config::default());
+        repl.handle_line("++++++++[>++++++++<-]>+.>,.", &mut screen, &mut keyboard)
+            .expect("handling a line should succeed");
+        screen.flush().expect("flushing should succeed");
+
+        assert_eq!(keyboard.into_inner().seen, Some(b"A".to_vec()));
+        assert_eq!(*screen.shown.borrow(), b"AB\n");
+    }
+
+    /// Only accepts a set number of bytes, then fails.
+    struct Full {
+        written: usize,
+        capacity: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written == self.capacity {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "full"));
+            }
+            let written = buf.len().min(self.capacity - self.written);
+            self.written += written;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Output should be written as the program runs, not held back until it stops.
+    #[test]
+    fn streamed_output() {
+        let mut full = Full {
+            written: 0,
+            capacity: 1000,
+        };
+
+        let mut repl: Repl<u8> = Repl::new(&Config::default());
+        repl.handle_line("+[.]", &mut full, &mut Cursor::new(vec![]))
+            .expect_err("handling a line should fail once the output is full");
+
+        assert_eq!(full.written, 1000);
+    }
+
+    #[test]
+    fn quit() {
+        let mut repl: Repl<u8> = Repl::new(&Config::default());
+        assert_eq!(
+            repl.handle_line(":quit", &mut vec![], &mut Cursor::new(vec![]))
+                .expect("handling a line should succeed"),
+            Flow::Quit
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use braincluck_interpreter::{backend, SourceMap};
+use clap::{builder::PossibleValuesParser, Args};
+
+use crate::{
+    exit_code,
+    options::{load_program, ConfigArgs},
+};
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// The file with the program to run. If this is `-` or missing, the program is read from stdin instead, and
+    /// gets no input.
+    file: Option<PathBuf>,
+
+    /// How to run the program. They all give the same output, but the optimizing ones count steps differently.
+    #[arg(
+        long,
+        default_value = "tree",
+        value_parser = PossibleValuesParser::new(backend::backends().iter().map(|backend| backend.name())),
+    )]
+    backend: String,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+pub fn run(args: RunArgs) -> ExitCode {
+    let (program, commands) = match load_program(args.file.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+
+    let mut out = io::stdout().lock();
+    let backend = backend::get(&args.backend).expect("clap should only allow built in backends");
+    let result = backend
+        .run(
+            &commands,
+            &mut out,
+            &mut io::stdin().lock(),
+            &args.config.config(),
+        )
+        .and_then(|_| Ok(out.flush()?));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Make sure everything the program wrote comes out before the error does.
+            let _ = out.flush();
+            let err = err.with_source_map(&SourceMap::new(&program));
+            eprintln!("error: {err}");
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
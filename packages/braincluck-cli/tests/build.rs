@@ -0,0 +1,128 @@
+//! Checks that `braincluck build` writes executables that run the program.
+
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// A directory for a test's files, which is removed once the test is done with it.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("braincluck-cli-{}-{name}", std::process::id()));
+        fs::create_dir_all(&path).expect("creating a directory should work");
+        Self(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs `braincluck build` with `args`, checking that it succeeds.
+fn build(args: &[&Path]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_braincluck"))
+        .arg("build")
+        .args(args)
+        .output()
+        .expect("running braincluck should work");
+
+    assert!(output.status.success(), "build should succeed: {output:?}");
+}
+
+fn run(executable: &Path) -> Output {
+    Command::new(executable)
+        .output()
+        .expect("running the executable should work")
+}
+
+/// The executable is named after the program's file by default.
+#[test]
+fn build_and_run() {
+    let dir = TempDir::new("build");
+    let source = dir.path().join("hello.b");
+    fs::write(&source, "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.")
+        .expect("writing the program should work");
+
+    build(&[&source]);
+    let output = run(&dir.path().join("hello"));
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"Hello World!\n");
+}
+
+/// `--output` and the interpreter settings should be followed.
+#[test]
+fn build_with_settings() {
+    let dir = TempDir::new("settings");
+    let source = dir.path().join("program.b");
+    let executable = dir.path().join("bounded");
+    fs::write(&source, "+.>>>").expect("writing the program should work");
+
+    build(&[
+        &source,
+        Path::new("-o"),
+        &executable,
+        Path::new("--tape-length"),
+        Path::new("2"),
+    ]);
+    let output = run(&executable);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, [1]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: pointer moved to cell 2, outside of a tape with 2 cells\n"
+    );
+}
+
+/// A program without an extension shouldn't be overwritten by its own executable.
+#[test]
+fn build_without_extension() {
+    let dir = TempDir::new("extensionless");
+    let source = dir.path().join("program");
+    fs::write(&source, "+++.").expect("writing the program should work");
+
+    build(&[&source]);
+    let output = run(&dir.path().join("program.out"));
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, [3]);
+    assert_eq!(
+        fs::read(&source).expect("reading the program should work"),
+        b"+++."
+    );
+}
+
+/// Building over the program itself should be refused.
+#[test]
+fn build_over_program() {
+    let dir = TempDir::new("overwrite");
+    let source = dir.path().join("program.b");
+    fs::write(&source, "+++.").expect("writing the program should work");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_braincluck"))
+        .arg("build")
+        .arg(&source)
+        .arg("-o")
+        .arg(&source)
+        .output()
+        .expect("running braincluck should work");
+
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(
+        fs::read(&source).expect("reading the program should work"),
+        b"+++."
+    );
+}
@@ -0,0 +1,335 @@
+//! A common interface over the different ways of running a program, so one can be picked by name at runtime.
+//!
+//! ```
+//! use braincluck_interpreter::{backend, bf_parse, Config};
+//!
+//! let commands = bf_parse("++++++++[>++++++++<-]>+.").unwrap();
+//! for backend in backend::backends() {
+//!     let mut out = vec![];
+//!     backend
+//!         .run(&commands, &mut out, &mut std::io::empty(), &Config::default())
+//!         .unwrap();
+//!     assert_eq!(out, b"A", "{}", backend.name());
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::{ir::Program, BraincluckError, CellValue, CellWidth, Cells, Command, Config, Vm};
+
+/// What a [`Backend`] reports about a program that ran to the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// How many steps the program took. Backends that optimize the program count fewer steps; see
+    /// [`Program`] for how.
+    pub steps: u64,
+    /// The index of the cell the pointer ended on.
+    pub position: usize,
+}
+
+impl Stats {
+    fn of<T: CellValue>(cells: &Cells<T>) -> Self {
+        Self {
+            steps: cells.steps(),
+            position: cells.index(),
+        }
+    }
+}
+
+/// A way of running programs, such as walking the command tree or compiling to machine code.
+///
+/// Every backend follows all of the settings in a [`Config`] and stops with the same errors, but may count
+/// steps differently, so a step limit can stop them at different places.
+pub trait Backend: Sync {
+    /// The name to find the backend by with [`get`].
+    fn name(&self) -> &'static str;
+
+    /// A short description of how the backend runs programs.
+    fn description(&self) -> &'static str;
+
+    /// Runs `commands` on a fresh tape with the settings in `config`.
+    ///
+    /// Runtime errors point at the failing command, but have no source location; use
+    /// [`BraincluckError::with_source_map`] to add one.
+    fn run(
+        &self,
+        commands: &[Command],
+        output: &mut dyn Write,
+        input: &mut dyn Read,
+        config: &Config,
+    ) -> Result<Stats, BraincluckError>;
+}
+
+/// Runs `$body` with `$cells` bound to fresh [`Cells`] of the type picked by the cell width in `$config`.
+macro_rules! with_cells {
+    ($config:expr, |$cells:pat_param| $body:expr) => {
+        match $config.cell_width {
+            CellWidth::I8 => {
+                let $cells: Cells<i8> = $config.cells();
+                $body
+            }
+            CellWidth::U8 => {
+                let $cells: Cells<u8> = $config.cells();
+                $body
+            }
+            CellWidth::U16 => {
+                let $cells: Cells<u16> = $config.cells();
+                $body
+            }
+            CellWidth::U32 => {
+                let $cells: Cells<u32> = $config.cells();
+                $body
+            }
+            CellWidth::I64 => {
+                let $cells: Cells<i64> = $config.cells();
+                $body
+            }
+        }
+    };
+}
+
+/// Walks the command tree with [`Cells::interpret`], the same as an [`Interpreter`](crate::Interpreter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TreeWalker;
+
+impl Backend for TreeWalker {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn description(&self) -> &'static str {
+        "Walks the command tree."
+    }
+
+    fn run(
+        &self,
+        commands: &[Command],
+        mut output: &mut dyn Write,
+        mut input: &mut dyn Read,
+        config: &Config,
+    ) -> Result<Stats, BraincluckError> {
+        with_cells!(config, |mut cells| {
+            cells.interpret(commands, &mut output, &mut input)?;
+            Ok(Stats::of(&cells))
+        })
+    }
+}
+
+/// Flattens the commands into jumps and runs them on a [`Vm`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Bytecode;
+
+impl Backend for Bytecode {
+    fn name(&self) -> &'static str {
+        "vm"
+    }
+
+    fn description(&self) -> &'static str {
+        "Runs flattened instructions on a virtual machine."
+    }
+
+    fn run(
+        &self,
+        commands: &[Command],
+        mut output: &mut dyn Write,
+        mut input: &mut dyn Read,
+        config: &Config,
+    ) -> Result<Stats, BraincluckError> {
+        with_cells!(config, |cells| {
+            let mut vm = Vm::new(commands, cells);
+            vm.run(&mut output, &mut input)?;
+            Ok(Stats::of(vm.cells()))
+        })
+    }
+}
+
+/// Optimizes the commands into a [`Program`] and runs that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Optimized;
+
+impl Backend for Optimized {
+    fn name(&self) -> &'static str {
+        "ir"
+    }
+
+    fn description(&self) -> &'static str {
+        "Runs an optimized program, with common loops replaced by single operations."
+    }
+
+    fn run(
+        &self,
+        commands: &[Command],
+        mut output: &mut dyn Write,
+        mut input: &mut dyn Read,
+        config: &Config,
+    ) -> Result<Stats, BraincluckError> {
+        let program = Program::new(commands);
+        with_cells!(config, |mut cells| {
+            program.run(&mut cells, &mut output, &mut input)?;
+            Ok(Stats::of(&cells))
+        })
+    }
+}
+
+/// Optimizes the commands into a [`Program`] and compiles that to machine code with a
+/// [`Jit`](crate::jit::Jit).
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Jitted;
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+impl Backend for Jitted {
+    fn name(&self) -> &'static str {
+        "jit"
+    }
+
+    fn description(&self) -> &'static str {
+        "Compiles an optimized program to x86-64 machine code."
+    }
+
+    fn run(
+        &self,
+        commands: &[Command],
+        mut output: &mut dyn Write,
+        mut input: &mut dyn Read,
+        config: &Config,
+    ) -> Result<Stats, BraincluckError> {
+        let program = Program::new(commands);
+        with_cells!(config, |mut cells| {
+            crate::jit::Jit::new(&program)?.run(&mut cells, &mut output, &mut input)?;
+            Ok(Stats::of(&cells))
+        })
+    }
+}
+
+static BACKENDS: &[&dyn Backend] = &[
+    &TreeWalker,
+    &Bytecode,
+    &Optimized,
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    &Jitted,
+];
+
+/// Returns every backend that was built in, starting with [`TreeWalker`], which is the default.
+pub fn backends() -> &'static [&'static dyn Backend] {
+    BACKENDS
+}
+
+/// Finds the backend called `name`, if it was built in.
+pub fn get(name: &str) -> Option<&'static dyn Backend> {
+    BACKENDS
+        .iter()
+        .copied()
+        .find(|backend| backend.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, mem};
+
+    use crate::{bf_parse, test_programs, BraincluckError, Config};
+
+    use super::{backends, get};
+
+    #[test]
+    fn registry() {
+        assert_eq!(backends()[0].name(), "tree");
+        for name in ["tree", "vm", "ir"] {
+            assert_eq!(get(name).map(|backend| backend.name()), Some(name));
+        }
+        assert!(get("nope").is_none());
+    }
+
+    /// Every backend should write the same output as the interpreter.
+    #[test]
+    fn esolang_programs() {
+        for (name, program, interpreter, input) in test_programs::programs() {
+            let expected = interpreter
+                .run_to_vec(program, input)
+                .expect("interpret should succeed");
+            let commands = bf_parse(program).expect("parsing returned an error");
+
+            for backend in backends() {
+                let mut out = vec![];
+                let result = backend.run(
+                    &commands,
+                    &mut out,
+                    &mut Cursor::new(input),
+                    interpreter.config(),
+                );
+
+                assert!(
+                    result.is_ok(),
+                    "{name} on {} failed: {result:?}",
+                    backend.name()
+                );
+                assert_eq!(
+                    out,
+                    expected,
+                    "{name} output on {} should match",
+                    backend.name()
+                );
+            }
+        }
+    }
+
+    /// Every backend should stop with the same kind of error as the interpreter. Step limits are counted
+    /// differently, so only the kind is compared.
+    #[test]
+    fn errors() {
+        for (name, program, interpreter) in test_programs::errors() {
+            let err = interpreter
+                .run_to_vec(program, b"")
+                .expect_err("interpret should fail");
+            let commands = bf_parse(program).expect("parsing returned an error");
+
+            for backend in backends() {
+                let result = backend.run(
+                    &commands,
+                    &mut vec![],
+                    &mut std::io::empty(),
+                    interpreter.config(),
+                );
+
+                let backend_err = match result {
+                    Ok(_) => panic!("{name} on {} should fail", backend.name()),
+                    Err(backend_err) => backend_err,
+                };
+                // Backends count steps differently, so only the kind of a step limit error has to match.
+                if let BraincluckError::StepLimitExceeded { .. } = err.inner() {
+                    assert_eq!(
+                        mem::discriminant(backend_err.inner()),
+                        mem::discriminant(err.inner()),
+                        "{name} on {}: {backend_err}",
+                        backend.name()
+                    );
+                } else {
+                    assert_eq!(
+                        backend_err.inner().to_string(),
+                        err.inner().to_string(),
+                        "{name} on {}",
+                        backend.name()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let commands = bf_parse("+>++>+++[-]<").expect("parsing returned an error");
+        for backend in backends() {
+            let stats = backend
+                .run(
+                    &commands,
+                    &mut vec![],
+                    &mut std::io::empty(),
+                    &Config::default(),
+                )
+                .expect("run should succeed");
+
+            assert_eq!(stats.position, 1, "{}", backend.name());
+            assert!(stats.steps > 0, "{}", backend.name());
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt::{Debug, Display};
+
+/// A value that can be stored in a memory cell.
+///
+/// This is implemented for [`i8`], [`u8`], [`u16`], [`u32`], and [`i64`], which lets [`Cells`](crate::Cells)
+/// emulate interpreters with 8, 16, 32, or 64 bit cells.
+///
+/// All arithmetic goes through [`i64`] and is truncated back to the cell width, which is the same as doing
+/// the arithmetic modulo `2^BITS`.
+pub trait CellValue: Copy + Default + Eq + Debug + Display {
+    /// The width of the cell, in bits.
+    const BITS: u32;
+
+    /// Converts this value to an [`i64`]. This is lossless for all implementors.
+    fn to_i64(self) -> i64;
+
+    /// Converts an [`i64`] to this type, truncating to the width of the cell.
+    fn from_i64_wrapping(value: i64) -> Self;
+
+    /// Converts an [`i64`] to this type, returning [`None`] if it does not fit.
+    fn from_i64_checked(value: i64) -> Option<Self>;
+
+    /// Converts an input byte to a cell value.
+    fn from_byte(byte: u8) -> Self {
+        Self::from_i64_wrapping(byte as i64)
+    }
+
+    /// Returns the lowest byte of the cell value.
+    fn to_byte(self) -> u8 {
+        self.to_i64() as u8
+    }
+
+    /// Adds `delta` to this value, wrapping around at the bounds of the type.
+    fn wrapping_offset(self, delta: i64) -> Self {
+        Self::from_i64_wrapping(self.to_i64().wrapping_add(delta))
+    }
+
+    /// Adds `delta` to this value, returning [`None`] if the result would not fit.
+    fn checked_offset(self, delta: i64) -> Option<Self> {
+        self.to_i64()
+            .checked_add(delta)
+            .and_then(Self::from_i64_checked)
+    }
+}
+
+macro_rules! impl_cell_value {
+    ($($ty:ty),*) => {
+        $(
+            impl CellValue for $ty {
+                const BITS: u32 = <$ty>::BITS;
+
+                fn to_i64(self) -> i64 {
+                    self as i64
+                }
+
+                fn from_i64_wrapping(value: i64) -> Self {
+                    value as $ty
+                }
+
+                fn from_i64_checked(value: i64) -> Option<Self> {
+                    <$ty>::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_cell_value!(i8, u8, u16, u32, i64);
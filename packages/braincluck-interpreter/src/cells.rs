@@ -1,93 +1,414 @@
-use std::{
-    collections::VecDeque,
-    io::{Read, Write},
-};
+use std::io::{ErrorKind, Read, Write};
 
-use crate::{BraincluckError, Command};
+use crate::{error::RuntimeError, BraincluckError, CellValue, Command};
+
+/// What the `,` command does when there is no more input to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofBehaviour {
+    /// Sets the current cell to zero.
+    Zero,
+    /// Sets the current cell to -1, or the maximum value for unsigned cells.
+    NegativeOne,
+    /// Leaves the current cell as-is.
+    Unchanged,
+    /// Stops with [`BraincluckError::UnexpectedEof`].
+    #[default]
+    Error,
+}
+
+/// How the `.` command writes the current cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Writes the lowest byte of the cell as-is.
+    #[default]
+    Byte,
+    /// Treats the cell as a character and writes it as UTF-8. Cells outside of `0..=255` will return
+    /// [`BraincluckError::ConversionError`].
+    Text,
+}
+
+/// How the tape behaves when the pointer moves past either end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TapeMode {
+    /// The tape grows in either direction as needed.
+    #[default]
+    Growable,
+    /// The tape has a fixed number of cells, and moving past either end returns
+    /// [`BraincluckError::PointerOutOfBounds`].
+    Bounded(usize),
+    /// The tape has a fixed number of cells, and moving past either end wraps around to the other end.
+    Circular(usize),
+}
+
+/// How the `+` and `-` commands handle going past the bounds of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArithmeticMode {
+    /// Cells wrap around, so incrementing the maximum value gives the minimum value and vice versa.
+    #[default]
+    Wrapping,
+    /// Going past the bounds of a cell returns [`BraincluckError::CellOverflow`].
+    Checked,
+}
 
 /// [`Cells`] are an array of memory cells that Brainfuck commands can be applied to.
-/// This array can continuously grow.
+/// By default, this array can continuously grow; see [`TapeMode`] for other options.
 ///
 /// To initialize, either initialize with `Cells::with_capacity` with your
 /// desired capacity, or `Cells::default()`, which will initialize the memory with the capacity
 /// for 30 000 cells.
 ///
-/// Implementation-wise, this is just a wrapper around a [`VecDeque`] with a tracked index.
+/// The width of each cell is determined by `T`, which defaults to 8 bit unsigned cells. For example,
+/// `Cells::<u16>::default()` will create 16 bit cells. See [`CellValue`] for the supported types.
+///
+/// Implementation-wise, this is a [`Vec`] with spare room on either side of the tape, and a tracked index.
 #[derive(Debug, Clone)]
-pub struct Cells {
-    memory: VecDeque<i8>, // TODO: Configurable bit size
+pub struct Cells<T: CellValue = u8> {
+    /// The tape, surrounded by zeroed cells that it can grow into without moving.
+    memory: Vec<T>,
+    /// Where the tape starts in `memory`.
+    start: usize,
+    /// Where the tape ends in `memory`.
+    end: usize,
     index: usize,
+    eof_behaviour: EofBehaviour,
+    output_mode: OutputMode,
+    tape_mode: TapeMode,
+    arithmetic_mode: ArithmeticMode,
+    steps: u64,
+    step_limit: Option<u64>,
 }
 
-impl Cells {
+impl<T: CellValue> Cells<T> {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             memory: {
-                let mut mem = VecDeque::with_capacity(capacity);
-                mem.push_back(0);
+                let mut mem = Vec::with_capacity(capacity);
+                mem.push(T::default());
                 mem
             },
+            start: 0,
+            end: 1,
             index: 0,
+            eof_behaviour: EofBehaviour::default(),
+            output_mode: OutputMode::default(),
+            tape_mode: TapeMode::default(),
+            arithmetic_mode: ArithmeticMode::default(),
+            steps: 0,
+            step_limit: None,
+        }
+    }
+
+    /// Sets what happens when the `,` command is executed with no input left.
+    pub fn with_eof_behaviour(mut self, eof_behaviour: EofBehaviour) -> Self {
+        self.eof_behaviour = eof_behaviour;
+        self
+    }
+
+    pub fn eof_behaviour(&self) -> EofBehaviour {
+        self.eof_behaviour
+    }
+
+    /// Sets how the `.` command writes the current cell.
+    pub fn with_output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode
+    }
+
+    /// Sets how the tape behaves when the pointer moves past either end. Fixed size tapes are only
+    /// allocated up to the furthest cell the pointer has reached, so a long tape costs nothing until it's used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a fixed size tape has a length of zero, or if the tape already has more cells than the
+    /// given length.
+    pub fn with_tape_mode(mut self, tape_mode: TapeMode) -> Self {
+        if let TapeMode::Bounded(length) | TapeMode::Circular(length) = tape_mode {
+            assert!(length > 0, "a fixed size tape must have at least one cell");
+            assert!(
+                self.end - self.start <= length,
+                "the tape already has more than {} cells",
+                length
+            );
+
+            // Fixed size tapes only grow to the right, so they start at the beginning of `memory`, and never
+            // need more room than their length.
+            self.memory.drain(..self.start);
+            self.memory.truncate(length);
+            self.end -= self.start;
+            self.start = 0;
         }
+
+        self.tape_mode = tape_mode;
+        self
+    }
+
+    pub fn tape_mode(&self) -> TapeMode {
+        self.tape_mode
+    }
+
+    /// Sets how the `+` and `-` commands handle going past the bounds of a cell.
+    pub fn with_arithmetic_mode(mut self, arithmetic_mode: ArithmeticMode) -> Self {
+        self.arithmetic_mode = arithmetic_mode;
+        self
+    }
+
+    pub fn arithmetic_mode(&self) -> ArithmeticMode {
+        self.arithmetic_mode
+    }
+
+    /// Sets the maximum number of steps that can be executed before stopping with
+    /// [`BraincluckError::StepLimitExceeded`]. Every command counts as a step, and so does every check of a
+    /// loop's condition.
+    ///
+    /// Steps are counted across calls, so the limit can be raised with [`Cells::set_step_limit`] or the count
+    /// cleared with [`Cells::reset_steps`] to keep going after the limit is hit.
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.step_limit = Some(step_limit);
+        self
     }
 
-    pub fn left(&mut self) {
-        if self.index == 0 {
-            self.memory.push_front(0);
-        } else {
-            self.index -= 1;
+    pub fn set_step_limit(&mut self, step_limit: Option<u64>) {
+        self.step_limit = step_limit;
+    }
+
+    pub fn step_limit(&self) -> Option<u64> {
+        self.step_limit
+    }
+
+    /// Returns the number of steps executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn reset_steps(&mut self) {
+        self.steps = 0;
+    }
+
+    /// Counts a single step, returning an error if this would go past the step limit.
+    pub(crate) fn count_step(&mut self) -> Result<(), BraincluckError> {
+        if matches!(self.step_limit, Some(limit) if self.steps >= limit) {
+            return Err(BraincluckError::StepLimitExceeded {
+                steps: self.steps,
+                position: self.index,
+            });
         }
+
+        self.steps += 1;
+        Ok(())
     }
 
-    pub fn right(&mut self) {
-        if self.index < isize::MAX as usize {
-            self.index += 1;
-            if self.index == self.memory.len() {
-                self.memory.push_back(0);
+    pub fn left(&mut self) -> Result<(), BraincluckError> {
+        self.move_by(-1)
+    }
+
+    pub fn right(&mut self) -> Result<(), BraincluckError> {
+        self.move_by(1)
+    }
+
+    /// Moves the pointer by `offset` cells, following the set [`TapeMode`].
+    ///
+    /// If this leaves a bounded tape, the error points at the first cell outside of it, the same as moving one
+    /// cell at a time would. If the tape can't grow to reach the new cell, this returns an
+    /// [`ErrorKind::OutOfMemory`] error and leaves the pointer where it was.
+    pub fn move_by(&mut self, offset: isize) -> Result<(), BraincluckError> {
+        let position = (self.index as isize).saturating_add(offset);
+        match self.tape_mode {
+            TapeMode::Growable => {
+                if position < 0 {
+                    self.grow_left(position.unsigned_abs())?;
+                    self.index = 0;
+                } else {
+                    self.grow_to(position as usize)?;
+                    self.index = position as usize;
+                }
+            }
+            TapeMode::Bounded(length) => {
+                if position < 0 {
+                    return Err(BraincluckError::PointerOutOfBounds {
+                        position: -1,
+                        length,
+                    });
+                }
+                if position as usize >= length {
+                    return Err(BraincluckError::PointerOutOfBounds {
+                        position: length as isize,
+                        length,
+                    });
+                }
+                self.grow_to(position as usize)?;
+                self.index = position as usize;
+            }
+            TapeMode::Circular(length) => {
+                let position = (position as i128).rem_euclid(length as i128) as usize;
+                self.grow_to(position)?;
+                self.index = position;
             }
         }
+
+        Ok(())
     }
 
-    pub fn increment(&mut self) {
-        self.memory[self.index] = self.memory[self.index].wrapping_add(1);
+    /// Adds `extra` cells to the start of the tape. If there isn't enough room, the tape is moved along with at
+    /// least as much room as it takes up, so that growing one cell at a time doesn't move it every time.
+    fn grow_left(&mut self, extra: usize) -> Result<(), BraincluckError> {
+        if extra > self.start {
+            let room = (extra - self.start).max(self.memory.len());
+            let mut memory = Vec::new();
+            room.checked_add(self.memory.len())
+                .and_then(|length| memory.try_reserve_exact(length).ok())
+                .ok_or_else(out_of_memory)?;
+            memory.resize(room, T::default());
+            memory.extend_from_slice(&self.memory);
+
+            self.memory = memory;
+            self.start += room;
+            self.end += room;
+        }
+
+        self.start -= extra;
+        Ok(())
     }
 
-    pub fn decrement(&mut self) {
-        self.memory[self.index] = self.memory[self.index].wrapping_sub(1);
+    /// Adds cells to the end of the tape until it reaches `index`. If there isn't enough room, at least doubles
+    /// the room, up to the length of a fixed size tape.
+    fn grow_to(&mut self, index: usize) -> Result<(), BraincluckError> {
+        let end = self.start + index + 1;
+        if end > self.memory.len() {
+            let mut length = end.max(self.memory.len().saturating_mul(2));
+            if let TapeMode::Bounded(limit) | TapeMode::Circular(limit) = self.tape_mode {
+                length = length.min(limit);
+            }
+            self.memory
+                .try_reserve_exact(length - self.memory.len())
+                .map_err(|_| out_of_memory())?;
+            self.memory.resize(length, T::default());
+        }
+
+        self.end = self.end.max(end);
+        Ok(())
+    }
+
+    pub fn increment(&mut self) -> Result<(), BraincluckError> {
+        self.add(1)
+    }
+
+    pub fn decrement(&mut self) -> Result<(), BraincluckError> {
+        self.add(-1)
+    }
+
+    /// Adds `delta` to the current cell, following the set [`ArithmeticMode`].
+    pub fn add(&mut self, delta: i64) -> Result<(), BraincluckError> {
+        let cell = &mut self.memory[self.start + self.index];
+        *cell = match self.arithmetic_mode {
+            ArithmeticMode::Wrapping => cell.wrapping_offset(delta),
+            ArithmeticMode::Checked => {
+                cell.checked_offset(delta)
+                    .ok_or(BraincluckError::CellOverflow {
+                        index: self.index,
+                        command: if delta > 0 {
+                            Command::Increment
+                        } else {
+                            Command::Decrement
+                        },
+                    })?
+            }
+        };
+
+        Ok(())
     }
 
     pub fn output(&self) -> Result<char, BraincluckError> {
-        Ok(u8::try_from(self.memory[self.index]).map(|num| char::from(num))?)
+        Ok(u8::try_from(self.current().to_i64()).map(char::from)?)
     }
 
-    pub fn input(&mut self, input: i8) {
-        self.memory[self.index] = input;
+    /// Writes the current cell to `output`, following the set [`OutputMode`].
+    pub fn write_output<W: Write>(&self, output: &mut W) -> Result<(), BraincluckError> {
+        match self.output_mode {
+            OutputMode::Byte => output.write_all(&[self.current().to_byte()])?,
+            OutputMode::Text => write!(output, "{}", self.output()?)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn input(&mut self, input: T) {
+        self.memory[self.start + self.index] = input;
+    }
+
+    /// Reads a single byte from `input` into the current cell, following the
+    /// set [`EofBehaviour`] if there is nothing left to read.
+    pub fn read_input<R: Read>(&mut self, input: &mut R) -> Result<(), BraincluckError> {
+        let mut buf = [0];
+        match input.read_exact(&mut buf) {
+            Ok(()) => self.input(T::from_byte(buf[0])),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => match self.eof_behaviour {
+                EofBehaviour::Zero => self.input(T::default()),
+                EofBehaviour::NegativeOne => self.input(T::from_i64_wrapping(-1)),
+                EofBehaviour::Unchanged => {}
+                EofBehaviour::Error => return Err(BraincluckError::UnexpectedEof),
+            },
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(())
     }
 
-    pub fn current(&self) -> i8 {
-        self.memory[self.index]
+    pub fn current(&self) -> T {
+        self.memory[self.start + self.index]
     }
 
     pub fn is_current_cell_zero(&self) -> bool {
-        self.memory[self.index] == 0
+        self.current() == T::default()
     }
 
-    pub fn cells(&self) -> &VecDeque<i8> {
-        &self.memory
+    pub fn cells(&self) -> &[T] {
+        &self.memory[self.start..self.end]
+    }
+
+    /// Returns the position of the pointer in [`Cells::cells`].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(crate) fn set_steps(&mut self, steps: u64) {
+        self.steps = steps;
+    }
+
+    /// Returns the memory the tape is kept in, including the zeroed cells on either side of it, along with where
+    /// the tape starts and ends in it and where the pointer is.
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) fn memory_mut(&mut self) -> (&mut [T], usize, usize, usize) {
+        let index = self.start + self.index;
+        (&mut self.memory, self.start, self.end, index)
+    }
+
+    /// Sets where the tape starts and ends in the memory returned by [`Cells::memory_mut`], and where the pointer
+    /// is, after the tape was widened into the zeroed cells.
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) fn set_memory_position(&mut self, start: usize, end: usize, index: usize) {
+        self.start = start;
+        self.end = end;
+        self.index = index - start;
     }
 
     /// Given a list of commands, applies commands.
+    ///
+    /// If a command fails, the error is returned as a [`BraincluckError::Runtime`], which has the state of the
+    /// cells when it failed and the index of the failing command character in `commands` (see
+    /// [`SourceMap`](crate::SourceMap)).
     pub fn interpret<W: Write, R: Read>(
         &mut self,
         commands: &[Command],
         output: &mut W,
         input: &mut R,
     ) -> Result<(), BraincluckError> {
-        for command in commands {
-            self.execute(command, output, input)?;
-        }
-
-        Ok(())
+        let mut blocks = vec![(commands, 0, false)];
+        self.walk(&mut blocks, output, input)
+            .map_err(|err| self.runtime_error(err, instruction_index(&blocks)))
     }
 
     /// Executes a single command.
@@ -97,29 +418,98 @@ impl Cells {
         output: &mut W,
         input: &mut R,
     ) -> Result<(), BraincluckError> {
-        match command {
-            Command::Right => self.right(),
-            Command::Left => self.left(),
-            Command::Increment => self.increment(),
-            Command::Decrement => self.decrement(),
-            Command::Output => write!(output, "{}", self.output()?)?,
-            Command::Input => {
-                let mut buf = [0];
-                input.read_exact(&mut buf)?;
-                self.input(buf[0] as i8);
-            }
-            Command::Jump(block) => {
-                while !self.is_current_cell_zero() {
-                    self.interpret(block, output, input)?;
+        self.interpret(std::slice::from_ref(command), output, input)
+    }
+
+    /// Runs the commands in `blocks`, which is a stack of the blocks being run, the index of the next command
+    /// in each, and whether the block is the body of a loop.
+    ///
+    /// Loops are run with this explicit stack rather than recursively, so deeply nested loops can't overflow
+    /// the native stack. If a command fails, `blocks` is left pointing at it.
+    fn walk<'a, W: Write, R: Read>(
+        &mut self,
+        blocks: &mut Vec<(&'a [Command], usize, bool)>,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        while let Some((block, index, is_loop)) = blocks.last_mut() {
+            let block: &'a [Command] = block;
+            match block.get(*index) {
+                Some(Command::Jump(inner)) => {
+                    self.count_step()?;
+                    *index += 1;
+                    if !self.is_current_cell_zero() {
+                        blocks.push((inner, 0, true));
+                    }
+                }
+                Some(command) => {
+                    self.count_step()?;
+                    match command {
+                        Command::Right => self.right()?,
+                        Command::Left => self.left()?,
+                        Command::Increment => self.increment()?,
+                        Command::Decrement => self.decrement()?,
+                        Command::Output => self.write_output(output)?,
+                        Command::Input => self.read_input(input)?,
+                        Command::Jump(_) => unreachable!("loops are handled above"),
+                    }
+                    *index += 1;
+                }
+                None => {
+                    if !*is_loop {
+                        break;
+                    }
+
+                    self.count_step()?;
+                    if self.is_current_cell_zero() {
+                        blocks.pop();
+                    } else {
+                        *index = 0;
+                    }
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Wraps an error from running the command character at `instruction` with the current state of the cells.
+    pub(crate) fn runtime_error(
+        &self,
+        error: BraincluckError,
+        instruction: usize,
+    ) -> BraincluckError {
+        BraincluckError::Runtime(Box::new(RuntimeError {
+            error,
+            index: self.index,
+            value: self.current().to_i64(),
+            steps: self.steps,
+            instruction,
+            span: None,
+        }))
+    }
+}
+
+/// The error for a tape that can't grow to reach a cell.
+fn out_of_memory() -> BraincluckError {
+    std::io::Error::from(ErrorKind::OutOfMemory).into()
+}
+
+/// Returns the index of the command character that the stack of blocks in [`Cells::walk`] points at.
+fn instruction_index(blocks: &[(&[Command], usize, bool)]) -> usize {
+    let mut instruction = 0;
+    if let Some(((block, index, _), outer)) = blocks.split_last() {
+        // Every outer block points just past the loop being run, so count up to and including its `[`.
+        for (outer_block, outer_index, _) in outer {
+            instruction += Command::count_instructions(&outer_block[..outer_index - 1]) + 1;
+        }
+        instruction += Command::count_instructions(&block[..*index]);
+    }
+
+    instruction
 }
 
-impl Default for Cells {
+impl<T: CellValue> Default for Cells<T> {
     fn default() -> Self {
         Self::with_capacity(30000)
     }
@@ -129,34 +519,30 @@ impl Default for Cells {
 #[cfg(test)]
 mod tests {
 
-    use std::io::{BufWriter, Cursor};
+    use std::io::Cursor;
 
-    use crate::{bf_parse, Cells};
+    use crate::{
+        bf_parse, bf_parse_with_source_map, ArithmeticMode, BraincluckError, CellWidth, Cells,
+        Command, EofBehaviour, Interpreter, OutputMode, TapeMode,
+    };
+
+    use crate::test_programs::{
+        CELL_SIZE, HELLO_WORLD, SHORT_HELLO_WORLD, TRICKY_HELLO_WORLD, WRAPPING_HELLO_WORLD,
+    };
 
     /// Straightforward hello world.
     #[test]
     fn hello_world() {
-        let mut cells = Cells::default();
-        let program = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
-        let commands = bf_parse(program).expect("hello world parsing returned an error");
-
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![];
-        let mut cursor = Cursor::new(input);
+        let interpreter = Interpreter::default();
+        let program = HELLO_WORLD;
 
-        cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
-            .expect("interpret should succeed");
+        let out = interpreter
+            .run_to_vec(program, &[])
+            .expect("run should succeed");
 
         assert_eq!(
             "Hello World!\n".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            String::from_utf8(out).expect("string should be valid utf8"),
             "outputs should match"
         );
     }
@@ -164,28 +550,16 @@ mod tests {
     /// This test can fail if cell values cannot be set below zero.
     #[test]
     fn tricky_hello_world() {
-        let mut cells = Cells::default();
-        let program = ">++++++++[-<+++++++++>]<.>>+>-[+]++>++>+++[>[->+++<<+++>]<<]>-----.>->
-        +++..+++.>-.<<+[>[+>+]>>]<--------------.>>.+++.------.--------.>+.>+.";
-        let commands = bf_parse(program).expect("tricky hello world parsing returned an error");
+        let interpreter = Interpreter::default();
+        let program = TRICKY_HELLO_WORLD;
 
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![];
-        let mut cursor = Cursor::new(input);
-
-        cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
-            .expect("interpret should succeed");
+        let out = interpreter
+            .run_to_vec(program, &[])
+            .expect("run should succeed");
 
         assert_eq!(
             "Hello World!\n".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            String::from_utf8(out).expect("string should be valid utf8"),
             "outputs should match"
         );
     }
@@ -193,28 +567,16 @@ mod tests {
     /// A program to write hello world but requires wrapping cells.
     #[test]
     fn wrapping_hello_world() {
-        let mut cells = Cells::default();
-        let program =
-            "--<-<<+[+[<+>--->->->-<<<]>]<<--.<++++++.<<-..<<.<+.>>.>>.<<<.+++.>>.>>-.<<<+.";
-        let commands = bf_parse(program).expect("wrapping hello world parsing returned an error");
+        let interpreter = Interpreter::default();
+        let program = WRAPPING_HELLO_WORLD;
 
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![];
-        let mut cursor = Cursor::new(input);
-
-        cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
-            .expect("interpret should succeed");
+        let out = interpreter
+            .run_to_vec(program, &[])
+            .expect("run should succeed");
 
         assert_eq!(
             "Hello, World!".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            String::from_utf8(out).expect("string should be valid utf8"),
             "outputs should match"
         );
     }
@@ -222,77 +584,56 @@ mod tests {
     /// A program to write hello world using the shortest code golf example. Requires wrapping cells.
     #[test]
     fn short_hello_world() {
-        let mut cells = Cells::default();
-        let program = "+[-->-[>>+>-----<<]<--<---]>-.>>>+.>>..+++[.>]<<<<.+++.------.<<-.>>>>+.";
-        let commands = bf_parse(program).expect("short hello world parsing returned an error");
+        let interpreter = Interpreter::default();
+        let program = SHORT_HELLO_WORLD;
 
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![];
-        let mut cursor = Cursor::new(input);
-
-        cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
-            .expect("interpret should succeed");
+        let out = interpreter
+            .run_to_vec(program, &[])
+            .expect("run should succeed");
 
         assert_eq!(
             "Hello, World!".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            String::from_utf8(out).expect("string should be valid utf8"),
             "outputs should match"
         );
     }
 
+    /// Runs [`CELL_SIZE`] with the given cell width.
+    fn cell_size_output(cell_width: CellWidth) -> String {
+        let out = Interpreter::default()
+            .with_cell_width(cell_width)
+            .run_to_vec(CELL_SIZE, &[])
+            .expect("run should succeed");
+
+        String::from_utf8(out).expect("string should be valid utf8")
+    }
+
     /// Tests obtaining the cell size.
     #[test]
     fn cell_size() {
-        let mut cells = Cells::default();
-        let program = r##"
-        Calculate the value 256 and test if it's zero
-        If the interpreter errors on overflow this is where it'll happen
-        ++++++++[>++++++++<-]>[<++++>-]
-        +<[>-<
-            Not zero so multiply by 256 again to get 65536
-            [>++++<-]>[<++++++++>-]<[>++++++++<-]
-            +>[>
-                # Print "32"
-                ++++++++++[>+++++<-]>+.-.[-]<
-            <[-]<->] <[>>
-                # Print "16"
-                +++++++[>+++++++<-]>.+++++.[-]<
-        <<-]] >[>
-            # Print "8"
-            ++++++++[>+++++++<-]>.[-]<
-        <-]<
-        # Print " bit cells\n"
-        +++++++++++[>+++>+++++++++>+++++++++>+<<<<-]>-.>-.+++++++.+++++++++++.<.
-        >>.++.+++++++..<-.>>-
-        Clean up used cells.
-        [[-]<]
-        "##;
-        let commands = bf_parse(program).expect("cell size parsing returned an error");
-
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![];
-        let mut cursor = Cursor::new(input);
+        assert_eq!(
+            "8 bit cells\n".to_string(),
+            cell_size_output(CellWidth::U8),
+            "outputs should match"
+        );
+    }
 
-        cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
-            .expect("interpret should succeed");
+    /// Tests obtaining the cell size with 16 bit cells.
+    #[test]
+    fn cell_size_16() {
+        assert_eq!(
+            "16 bit cells\n".to_string(),
+            cell_size_output(CellWidth::U16),
+            "outputs should match"
+        );
+    }
 
+    /// Tests obtaining the cell size with 32 bit cells.
+    #[test]
+    fn cell_size_32() {
         assert_eq!(
-            "8 bit cells\n".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            "32 bit cells\n".to_string(),
+            cell_size_output(CellWidth::U32),
             "outputs should match"
         );
     }
@@ -300,29 +641,16 @@ mod tests {
     /// A cat program where EOF returns 0.
     #[test]
     fn cat_zero() {
-        let mut cells = Cells::default();
+        let interpreter = Interpreter::default();
         let program = ",[.,]";
-        let commands = bf_parse(program).expect("cat zero parsing returned an error");
 
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![
-            'H' as u8, 'e' as u8, 'l' as u8, 'l' as u8, 'o' as u8, '!' as u8, 0,
-        ];
-        let mut cursor = Cursor::new(input);
-
-        cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
-            .expect("interpret should succeed");
+        let out = interpreter
+            .run_to_vec(program, &[b'H', b'e', b'l', b'l', b'o', b'!', 0])
+            .expect("run should succeed");
 
         assert_eq!(
             "Hello!".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            String::from_utf8(out).expect("string should be valid utf8"),
             "outputs should match"
         );
     }
@@ -330,36 +658,465 @@ mod tests {
     /// A cat program where EOF returns -1.
     #[test]
     fn cat_negative_one() {
-        let mut cells = Cells::default();
+        let interpreter = Interpreter::default();
+        let program = ",+[-.,+]";
+
+        let out = interpreter
+            .run_to_vec(program, &[b'H', b'e', b'l', b'l', b'o', b'!', -1_i8 as u8])
+            .expect("run should succeed");
+
+        assert_eq!(
+            "Hello!".to_string(),
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "outputs should match"
+        );
+    }
+
+    /// A cat program where EOF returns 0, without a sentinel byte in the input.
+    #[test]
+    fn cat_eof_zero() {
+        let interpreter = Interpreter::default().with_eof_behaviour(EofBehaviour::Zero);
+        let program = ",[.,]";
+
+        let out = interpreter
+            .run_to_vec(program, b"Hello!")
+            .expect("run should succeed");
+
+        assert_eq!(
+            "Hello!".to_string(),
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "outputs should match"
+        );
+    }
+
+    /// A cat program where EOF returns -1, without a sentinel byte in the input.
+    #[test]
+    fn cat_eof_negative_one() {
+        let interpreter = Interpreter::default().with_eof_behaviour(EofBehaviour::NegativeOne);
         let program = ",+[-.,+]";
-        let commands = bf_parse(program).expect("cat negative one parsing returned an error");
-
-        let out = vec![];
-        let mut buf_out = BufWriter::new(out);
-        let input = vec![
-            'H' as u8,
-            'e' as u8,
-            'l' as u8,
-            'l' as u8,
-            'o' as u8,
-            '!' as u8,
-            (-1 as i8) as u8,
-        ];
-        let mut cursor = Cursor::new(input);
+
+        let out = interpreter
+            .run_to_vec(program, b"Hello!")
+            .expect("run should succeed");
+
+        assert_eq!(
+            "Hello!".to_string(),
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "outputs should match"
+        );
+    }
+
+    /// EOF with -1 should set the maximum value on wider unsigned cells.
+    #[test]
+    fn eof_negative_one_wide() {
+        let mut cells: Cells<u16> = Cells::default().with_eof_behaviour(EofBehaviour::NegativeOne);
+        let commands = bf_parse(",").expect("parsing returned an error");
 
         cells
-            .interpret(&commands, &mut buf_out, &mut cursor)
+            .interpret(&commands, &mut vec![], &mut Cursor::new(vec![]))
             .expect("interpret should succeed");
 
+        assert_eq!(cells.current(), u16::MAX);
+    }
+
+    /// A cat program where EOF leaves the cell unchanged.
+    #[test]
+    fn cat_eof_unchanged() {
+        let interpreter = Interpreter::default().with_eof_behaviour(EofBehaviour::Unchanged);
+        let program = ",[.[-],]";
+
+        let out = interpreter
+            .run_to_vec(program, b"Hello!")
+            .expect("run should succeed");
+
         assert_eq!(
             "Hello!".to_string(),
-            String::from_utf8(
-                buf_out
-                    .into_inner()
-                    .expect("getting inner buffer should work")
-            )
-            .expect("string should be valid utf8"),
+            String::from_utf8(out).expect("string should be valid utf8"),
             "outputs should match"
         );
     }
+
+    /// Reading past the end of the input should return an error by default.
+    #[test]
+    fn eof_error() {
+        let mut cells: Cells = Cells::default();
+        let commands = bf_parse(",[.,]").expect("cat parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(b"Hi".to_vec()));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::UnexpectedEof)
+        ));
+    }
+
+    /// Cells above 127 should be written as a single raw byte by default.
+    #[test]
+    fn byte_output() {
+        let interpreter = Interpreter::default();
+
+        let out = interpreter
+            .run_to_vec("-.", &[])
+            .expect("run should succeed");
+
+        assert_eq!(out, vec![255]);
+    }
+
+    /// Negative cells should be written as their two's complement byte.
+    #[test]
+    fn byte_output_negative() {
+        let interpreter = Interpreter::default().with_cell_width(CellWidth::I8);
+
+        let out = interpreter
+            .run_to_vec("--.", &[])
+            .expect("run should succeed");
+
+        assert_eq!(out, vec![254]);
+    }
+
+    /// Text output should encode cells as UTF-8.
+    #[test]
+    fn text_output() {
+        let interpreter = Interpreter::default().with_output_mode(OutputMode::Text);
+
+        let out = interpreter
+            .run_to_vec("-.", &[])
+            .expect("run should succeed");
+
+        assert_eq!(
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "ÿ"
+        );
+    }
+
+    /// Text output should fail on cells that cannot be a character.
+    #[test]
+    fn text_output_out_of_range() {
+        let mut cells: Cells<i8> = Cells::default().with_output_mode(OutputMode::Text);
+        let commands = bf_parse("-.").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::ConversionError(_))
+        ));
+    }
+
+    /// Moving past the end of a bounded tape should return an error.
+    #[test]
+    fn bounded_tape_right() {
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Bounded(3));
+        let commands = bf_parse(">>+>").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::PointerOutOfBounds {
+                position: 3,
+                length: 3
+            })
+        ));
+        assert_eq!(cells.index(), 2);
+        assert_eq!(cells.current(), 1);
+    }
+
+    /// Moving before the start of a bounded tape should return an error.
+    #[test]
+    fn bounded_tape_left() {
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Bounded(30000));
+        let commands = bf_parse("<").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::PointerOutOfBounds {
+                position: -1,
+                length: 30000
+            })
+        ));
+    }
+
+    /// Hello world should still work on a classic bounded tape.
+    #[test]
+    fn bounded_tape_hello_world() {
+        let interpreter = Interpreter::default().with_tape_mode(TapeMode::Bounded(30000));
+        let program = HELLO_WORLD;
+
+        let out = interpreter
+            .run_to_vec(program, &[])
+            .expect("run should succeed");
+
+        assert_eq!(
+            "Hello World!\n".to_string(),
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "outputs should match"
+        );
+    }
+
+    /// Long fixed size tapes should only allocate the cells that are used.
+    #[test]
+    fn long_bounded_tape() {
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Bounded(usize::MAX));
+        let commands = bf_parse(HELLO_WORLD).expect("hello world parsing returned an error");
+
+        let mut out = vec![];
+        cells
+            .interpret(&commands, &mut out, &mut Cursor::new(vec![]))
+            .expect("interpret should succeed");
+
+        assert_eq!(out, b"Hello World!\n");
+        assert_eq!(cells.cells().len(), 7);
+    }
+
+    /// Wrapping around to the far end of a tape too long to allocate should fail instead of aborting.
+    #[test]
+    fn circular_tape_too_long() {
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Circular(usize::MAX));
+        let commands = bf_parse("+<").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::IOError(err)) if err.kind() == std::io::ErrorKind::OutOfMemory
+        ));
+        assert_eq!(cells.index(), 0);
+        assert_eq!(cells.current(), 1);
+    }
+
+    /// A circular tape should wrap the pointer around in both directions.
+    #[test]
+    fn circular_tape() {
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Circular(4));
+        let commands = bf_parse("<+>>+++<<<").expect("parsing returned an error");
+
+        cells
+            .interpret(&commands, &mut vec![], &mut Cursor::new(vec![]))
+            .expect("interpret should succeed");
+
+        assert_eq!(cells.index(), 2);
+        assert_eq!(cells.cells(), [0, 3, 0, 1]);
+    }
+
+    /// Checked arithmetic should not affect programs that don't depend on wrapping.
+    #[test]
+    fn checked_hello_world() {
+        let interpreter = Interpreter::default().with_arithmetic_mode(ArithmeticMode::Checked);
+        let program = HELLO_WORLD;
+
+        let out = interpreter
+            .run_to_vec(program, &[])
+            .expect("run should succeed");
+
+        assert_eq!(
+            "Hello World!\n".to_string(),
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "outputs should match"
+        );
+    }
+
+    /// Checked arithmetic should catch programs that require wrapping cells.
+    #[test]
+    fn checked_wrapping_hello_world() {
+        let mut cells: Cells = Cells::default().with_arithmetic_mode(ArithmeticMode::Checked);
+        let program = WRAPPING_HELLO_WORLD;
+        let commands = bf_parse(program).expect("wrapping hello world parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::CellOverflow {
+                index: 0,
+                command: Command::Decrement
+            })
+        ));
+    }
+
+    /// Checked arithmetic should catch going above the maximum value of a cell.
+    #[test]
+    fn checked_overflow() {
+        let mut cells: Cells<i8> = Cells::default().with_arithmetic_mode(ArithmeticMode::Checked);
+        let commands = bf_parse(">+[+]").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::CellOverflow {
+                index: 1,
+                command: Command::Increment
+            })
+        ));
+        assert_eq!(cells.current(), i8::MAX);
+    }
+
+    /// An infinite loop should stop once it runs out of steps.
+    #[test]
+    fn step_limit() {
+        let mut cells: Cells = Cells::default().with_step_limit(1000);
+        let commands = bf_parse(">+[]").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::StepLimitExceeded {
+                steps: 1000,
+                position: 1
+            })
+        ));
+        assert_eq!(cells.current(), 1);
+    }
+
+    /// A program that finishes within its step limit should run as usual.
+    #[test]
+    fn step_limit_hello_world() {
+        let mut cells: Cells = Cells::default().with_step_limit(10_000);
+        let program = HELLO_WORLD;
+        let commands = bf_parse(program).expect("hello world parsing returned an error");
+
+        let mut out = vec![];
+        cells
+            .interpret(&commands, &mut out, &mut Cursor::new(vec![]))
+            .expect("interpret should succeed");
+
+        assert_eq!(
+            "Hello World!\n".to_string(),
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "outputs should match"
+        );
+        assert!(cells.steps() <= 10_000);
+    }
+
+    /// Running out of steps should leave the tape intact, so it can be resumed.
+    #[test]
+    fn step_limit_resume() {
+        let mut cells: Cells = Cells::default().with_step_limit(3);
+        let commands = bf_parse("+++++").expect("parsing returned an error");
+
+        let result = cells.interpret(&commands, &mut vec![], &mut Cursor::new(vec![]));
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::StepLimitExceeded { steps: 3, .. })
+        ));
+        assert_eq!(cells.current(), 3);
+
+        cells.reset_steps();
+        cells
+            .interpret(&commands[3..], &mut vec![], &mut Cursor::new(vec![]))
+            .expect("interpret should succeed");
+        assert_eq!(cells.current(), 5);
+    }
+
+    /// Runtime errors should say where the program failed.
+    #[test]
+    fn runtime_error_context() {
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Bounded(3));
+        let program = "+++ set up\n>>>>";
+        let (commands, source_map) =
+            bf_parse_with_source_map(program).expect("parsing returned an error");
+
+        let err = cells
+            .interpret(&commands, &mut vec![], &mut Cursor::new(vec![]))
+            .expect_err("interpret should fail")
+            .with_source_map(&source_map);
+        match &err {
+            BraincluckError::Runtime(context) => {
+                assert!(matches!(
+                    context.error,
+                    BraincluckError::PointerOutOfBounds {
+                        position: 3,
+                        length: 3
+                    }
+                ));
+                assert_eq!(context.index, 2);
+                assert_eq!(context.value, 0);
+                assert_eq!(context.steps, 6);
+                assert_eq!(context.instruction, 5);
+                assert_eq!(
+                    context.span.map(|span| (span.line, span.column)),
+                    Some((2, 3))
+                );
+            }
+            err => panic!("expected a runtime error, got {err:?}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            "pointer moved to cell 3, outside of a tape with 3 cells (at cell 2 with value 0, after 6 steps, at line 2, column 3)"
+        );
+    }
+
+    /// The context shouldn't repeat the cell or steps when the error already says them.
+    #[test]
+    fn runtime_error_context_without_repeats() {
+        for (program, cells, message) in [
+            (
+                "+[]",
+                Cells::default().with_step_limit(100),
+                "stopped after 100 steps at cell 0 (with value 1, at line 1, column 3)",
+            ),
+            (
+                ">-",
+                Cells::default().with_arithmetic_mode(ArithmeticMode::Checked),
+                "cell 1 went out of bounds while executing `Decrement` (with value 0, after 2 steps, at line 1, column 2)",
+            ),
+        ] {
+            let mut cells: Cells = cells;
+            let (commands, source_map) =
+                bf_parse_with_source_map(program).expect("parsing returned an error");
+
+            let err = cells
+                .interpret(&commands, &mut vec![], &mut Cursor::new(vec![]))
+                .expect_err("interpret should fail")
+                .with_source_map(&source_map);
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    /// Errors inside of loops, including at the closing bracket, should point at the right command.
+    #[test]
+    fn runtime_error_in_loop() {
+        for (program, cells, instruction) in [
+            (
+                "+[>+]",
+                Cells::default().with_tape_mode(TapeMode::Bounded(3)),
+                2,
+            ),
+            ("+[]", Cells::default().with_step_limit(2), 2),
+            ("+[[-]+[]]", Cells::default().with_step_limit(9), 7),
+        ] {
+            let mut cells: Cells = cells;
+            let commands = bf_parse(program).expect("parsing returned an error");
+
+            let err = cells
+                .interpret(&commands, &mut vec![], &mut Cursor::new(vec![]))
+                .expect_err("interpret should fail");
+            match err {
+                BraincluckError::Runtime(context) => {
+                    assert_eq!(context.instruction, instruction, "{program}")
+                }
+                err => panic!("expected a runtime error, got {err:?}"),
+            }
+        }
+    }
+
+    /// Deeply nested loops should not overflow the stack.
+    #[test]
+    fn deeply_nested() {
+        let mut cells: Cells = Cells::default();
+        let program = format!("+{}-{}.", "[".repeat(100_000), "]".repeat(100_000));
+        let commands = bf_parse(&program).expect("deeply nested parsing returned an error");
+
+        let mut out = vec![];
+        cells
+            .interpret(&commands, &mut out, &mut Cursor::new(vec![]))
+            .expect("interpret should succeed");
+
+        assert_eq!(out, vec![0]);
+        assert_eq!(cells.steps(), 200_003);
+    }
 }
@@ -0,0 +1,720 @@
+//! Compiles programs to a readable x86-64 assembly listing for Linux, in either GNU assembler or NASM syntax.
+//!
+//! Each block of instructions is commented with the Brainfuck commands it was compiled from, so the listing can
+//! be read side by side with the source. The listing builds into the same kind of standalone executable as
+//! [`elf`](super::elf), using raw system calls and no C library:
+//!
+//! ```bash
+//! as -o hello.o hello.s && ld -o hello hello.o
+//! nasm -f elf64 -o hello.o hello.asm && ld -o hello hello.o
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    ir::{Op, Program},
+    BraincluckError, CellWidth, Config, EofBehaviour, OutputMode, SourceMap, TapeMode,
+};
+
+use super::{
+    linux::{self, Message, Routine, BUFFER_SIZE, GROWABLE_SIZE},
+    require_wrapping,
+};
+
+/// Which assembler the listing is written for. Both use Intel syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// The GNU assembler, `as`.
+    #[default]
+    Gas,
+    /// The Netwide Assembler, `nasm`.
+    Nasm,
+}
+
+/// Compiles `program` into an x86-64 assembly listing, annotated with the commands in `source` that each block
+/// came from. `source` should be the code that `program` was parsed from.
+///
+/// The built program reads from stdin and writes to stdout, and errors are written to stderr with the same
+/// messages as the interpreter, exiting with a code of 1. Checked arithmetic is not supported.
+pub fn generate(
+    program: &Program,
+    config: &Config,
+    source: &str,
+    syntax: Syntax,
+) -> Result<String, BraincluckError> {
+    require_wrapping(config)?;
+
+    let width = linux::width(config);
+    let size = match width {
+        1 => "byte",
+        2 => "word",
+        4 => "dword",
+        _ => "qword",
+    };
+    let ptr = match syntax {
+        Syntax::Gas => " ptr",
+        Syntax::Nasm => "",
+    };
+    let register = width.trailing_zeros() as usize;
+
+    let mut listing = Listing {
+        code: String::new(),
+        config: *config,
+        syntax,
+        width,
+        cell: format!("{size}{ptr} [r12 + r13*{width}]"),
+        rax: ["al", "ax", "eax", "rax"][register],
+        r8: ["r8b", "r8w", "r8d", "r8"][register],
+        labels: 0,
+    };
+    listing.program(program, source)?;
+
+    Ok(listing.code)
+}
+
+/// Writes the listing. The registers are used as described in [`linux`], the same as [`elf`](super::elf).
+struct Listing {
+    code: String,
+    config: Config,
+    syntax: Syntax,
+    width: u32,
+    /// The current cell, as a memory operand.
+    cell: String,
+    /// rax and r8, sized to fit a cell.
+    rax: &'static str,
+    r8: &'static str,
+    /// How many labels have been made.
+    labels: usize,
+}
+
+impl Listing {
+    fn line(&mut self, instruction: &str) -> Result<(), BraincluckError> {
+        writeln!(self.code, "    {instruction}")?;
+        Ok(())
+    }
+
+    /// Writes an instruction with a comment after it.
+    fn line_with(&mut self, instruction: &str, comment: &str) -> Result<(), BraincluckError> {
+        let marker = match self.syntax {
+            Syntax::Gas => "#",
+            Syntax::Nasm => ";",
+        };
+        writeln!(self.code, "    {instruction:<24}{marker} {comment}")?;
+        Ok(())
+    }
+
+    fn comment(&mut self, comment: &str) -> Result<(), BraincluckError> {
+        let marker = match self.syntax {
+            Syntax::Gas => "#",
+            Syntax::Nasm => ";",
+        };
+        writeln!(self.code, "    {marker} {comment}")?;
+        Ok(())
+    }
+
+    fn label(&mut self, label: &str) -> Result<(), BraincluckError> {
+        writeln!(self.code, "{label}:")?;
+        Ok(())
+    }
+
+    /// Writes a call or jump to a routine.
+    fn call(&mut self, instruction: &str, routine: Routine) -> Result<(), BraincluckError> {
+        self.line(&format!("{instruction} {}", routine.label()))
+    }
+
+    /// Returns a new label starting with `name`.
+    fn new_label(&mut self, name: &str) -> String {
+        self.labels += 1;
+        format!("{name}_{}", self.labels)
+    }
+
+    fn program(&mut self, program: &Program, source: &str) -> Result<(), BraincluckError> {
+        let source_map = SourceMap::new(source);
+        match self.syntax {
+            Syntax::Gas => {
+                writeln!(self.code, "# Generated by braincluck.")?;
+                writeln!(self.code, ".intel_syntax noprefix")?;
+                writeln!(self.code, ".globl _start")?;
+                writeln!(self.code, ".text")?;
+            }
+            Syntax::Nasm => {
+                writeln!(self.code, "; Generated by braincluck.")?;
+                writeln!(self.code, "bits 64")?;
+                writeln!(self.code, "global _start")?;
+                writeln!(self.code, "section .text")?;
+            }
+        }
+        writeln!(self.code)?;
+
+        let (size, start, r14) = linux::tape(&self.config);
+        self.label("_start")?;
+        self.comment("Map the output buffer and the tape, which start out zeroed.")?;
+        self.line_with("mov eax, 9", "mmap")?;
+        self.line("xor edi, edi")?;
+        self.line(&format!("mov rsi, {}", BUFFER_SIZE as u64 + size))?;
+        self.line_with("mov edx, 3", "PROT_READ | PROT_WRITE")?;
+        self.line_with(
+            "mov r10d, 0x4022",
+            "MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE",
+        )?;
+        self.line("mov r8, -1")?;
+        self.line("xor r9d, r9d")?;
+        self.line("syscall")?;
+        self.line("cmp rax, -4095")?;
+        self.call("jae", Routine::OutOfMemory)?;
+        self.line_with("mov rbx, rax", "the output buffer")?;
+        self.line_with(&format!("lea r12, [rax + {BUFFER_SIZE}]"), "the tape")?;
+        self.line("xor ebp, ebp")?;
+        self.line_with(&format!("mov r13, {start}"), "the index")?;
+        let r14_comment = match self.config.tape_mode {
+            TapeMode::Growable => "the lowest index reached",
+            _ => "the length of the tape",
+        };
+        self.line_with(&format!("mov r14, {r14}"), r14_comment)?;
+        self.line_with("xor r15d, r15d", "the steps")?;
+        if let Some(step_limit) = self.config.step_limit {
+            self.line(&format!("mov r9, {step_limit}"))?;
+        }
+
+        let sources = program.sources();
+        let mut loops = vec![];
+        for (i, op) in program.ops().iter().enumerate() {
+            if i == 0 || sources[i] != sources[i - 1] {
+                // An op covers every command up to the next op that starts somewhere else.
+                let end = sources[i + 1..]
+                    .iter()
+                    .copied()
+                    .find(|&next| next > sources[i])
+                    .unwrap_or(source_map.len());
+                self.annotate(source, &source_map, sources[i], end)?;
+            }
+
+            self.step()?;
+            match *op {
+                Op::Add(delta) => self.add(delta)?,
+                Op::Move(offset) => self.move_by(offset)?,
+                Op::Output => self.output()?,
+                Op::Input => self.input()?,
+                Op::LoopStart(_) => {
+                    let body = self.new_label("loop");
+                    let end = format!("{body}_end");
+                    self.line(&format!("cmp {}, 0", self.cell))?;
+                    self.line(&format!("je {end}"))?;
+                    self.label(&body)?;
+                    loops.push((body, end));
+                }
+                Op::LoopEnd(_) => {
+                    let (body, end) = loops.pop().expect("loops should be balanced");
+                    self.line(&format!("cmp {}, 0", self.cell))?;
+                    self.line(&format!("jne {body}"))?;
+                    self.label(&end)?;
+                }
+                Op::Clear(_) => self.line(&format!("mov {}, 0", self.cell))?,
+                Op::Scan(offset) => {
+                    let scan = self.new_label("scan");
+                    let end = format!("{scan}_end");
+                    self.label(&scan)?;
+                    self.line(&format!("cmp {}, 0", self.cell))?;
+                    self.line(&format!("je {end}"))?;
+                    self.step()?;
+                    self.move_by(offset)?;
+                    self.line(&format!("jmp {scan}"))?;
+                    self.label(&end)?;
+                }
+                Op::MulAdd { offset, factor } => {
+                    let skip = self.new_label("mul_add_skip");
+                    self.line(&format!("cmp {}, 0", self.cell))?;
+                    self.line(&format!("je {skip}"))?;
+                    self.line(&format!("mov {}, {}", self.rax, self.cell))?;
+                    if i32::try_from(factor).is_ok() {
+                        self.line(&format!("imul rax, rax, {factor}"))?;
+                    } else {
+                        self.line(&format!("mov rdx, {factor}"))?;
+                        self.line("imul rax, rdx")?;
+                    }
+                    self.line("mov r8, rax")?;
+                    self.move_by(offset)?;
+                    self.line(&format!("add {}, {}", self.cell, self.r8))?;
+                    self.move_by(-offset)?;
+                    self.label(&skip)?;
+                }
+            }
+        }
+
+        writeln!(self.code)?;
+        self.comment("The end of the program.")?;
+        self.call("call", Routine::Flush)?;
+        self.line_with("mov eax, 231", "exit_group")?;
+        self.line("xor edi, edi")?;
+        self.line("syscall")?;
+
+        self.routines()?;
+        self.messages()
+    }
+
+    /// Comments with the commands from `start` up to `end`, and where they start, leaving out any comments in
+    /// the source.
+    fn annotate(
+        &mut self,
+        source: &str,
+        source_map: &SourceMap,
+        start: usize,
+        end: usize,
+    ) -> Result<(), BraincluckError> {
+        let Some(first) = source_map.get(start) else {
+            return Ok(());
+        };
+        let commands: String = source_map.spans()[start..end.min(source_map.len())]
+            .iter()
+            .map(|span| &source[span.start..span.end])
+            .collect();
+
+        writeln!(self.code)?;
+        self.comment(&format!(
+            "line {}, column {}: {commands}",
+            first.line, first.column
+        ))
+    }
+
+    /// Counts a step, stopping if this goes over the step limit.
+    fn step(&mut self) -> Result<(), BraincluckError> {
+        if self.config.step_limit.is_some() {
+            self.line("inc r15")?;
+            self.line("cmp r15, r9")?;
+            self.call("ja", Routine::StepLimitExceeded)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `delta` to the current cell, wrapping around.
+    fn add(&mut self, delta: i64) -> Result<(), BraincluckError> {
+        // Immediates are at most 32 bits, and have to fit in the cell.
+        let immediate = match self.width {
+            1 => delta as i8 as i64,
+            2 => delta as i16 as i64,
+            _ => delta as i32 as i64,
+        };
+        if immediate == delta || self.width < 8 {
+            self.line(&format!("add {}, {immediate}", self.cell))
+        } else {
+            self.line(&format!("mov rax, {delta}"))?;
+            self.line(&format!("add {}, rax", self.cell))
+        }
+    }
+
+    /// Moves the pointer, following the [`TapeMode`]. Clobbers rax and rdx.
+    fn move_by(&mut self, offset: isize) -> Result<(), BraincluckError> {
+        if i32::try_from(offset).is_ok() {
+            let sign = if offset < 0 { '-' } else { '+' };
+            self.line(&format!("lea rax, [r13 {sign} {}]", offset.unsigned_abs()))?;
+        } else {
+            self.line(&format!("mov rax, {offset}"))?;
+            self.line("add rax, r13")?;
+        }
+
+        match self.config.tape_mode {
+            TapeMode::Growable if offset > 0 => {
+                self.line(&format!("cmp rax, {}", GROWABLE_SIZE / self.width as u64))?;
+                self.call("jae", Routine::OutOfMemory)?;
+            }
+            // Moving left past the lowest index reached grows the tape.
+            TapeMode::Growable => {
+                let inside = self.new_label("move_inside");
+                self.line("cmp rax, r14")?;
+                self.line(&format!("jge {inside}"))?;
+                self.line("test rax, rax")?;
+                self.call("js", Routine::OutOfMemory)?;
+                self.line("mov r14, rax")?;
+                self.label(&inside)?;
+            }
+            TapeMode::Bounded(_) => {
+                self.line("cmp rax, r14")?;
+                self.call("jae", Routine::OutOfBounds)?;
+            }
+            TapeMode::Circular(_) => {
+                let inside = self.new_label("move_inside");
+                self.line("cmp rax, r14")?;
+                self.line(&format!("jb {inside}"))?;
+                self.line("cqo")?;
+                self.line("idiv r14")?;
+                self.line("mov rax, rdx")?;
+                self.line("test rax, rax")?;
+                self.line(&format!("jns {inside}"))?;
+                self.line("add rax, r14")?;
+                self.label(&inside)?;
+            }
+        }
+
+        self.line("mov r13, rax")
+    }
+
+    /// Writes the current cell, following the [`OutputMode`].
+    fn output(&mut self) -> Result<(), BraincluckError> {
+        match self.config.output_mode {
+            OutputMode::Byte => {
+                self.line(&format!("mov {}, {}", self.rax, self.cell))?;
+                self.call("call", Routine::Putc)
+            }
+            // Only 0 to 255 can be written, see the [module documentation](super).
+            OutputMode::Text => {
+                let load = match self.config.cell_width {
+                    CellWidth::I8 => "movsx rax,",
+                    CellWidth::U8 | CellWidth::U16 => "movzx eax,",
+                    CellWidth::U32 => "mov eax,",
+                    CellWidth::I64 => "mov rax,",
+                };
+                let ascii = self.new_label("output_ascii");
+                self.line(&format!("{load} {}", self.cell))?;
+                self.line("cmp rax, 255")?;
+                self.call("ja", Routine::Conversion)?;
+                self.line("cmp eax, 0x80")?;
+                self.line(&format!("jb {ascii}"))?;
+                self.line("mov r8, rax")?;
+                self.line("shr eax, 6")?;
+                self.line("or eax, 0xc0")?;
+                self.call("call", Routine::Putc)?;
+                self.line("mov rax, r8")?;
+                self.line("and eax, 0x3f")?;
+                self.line("or eax, 0x80")?;
+                self.label(&ascii)?;
+                self.call("call", Routine::Putc)
+            }
+        }
+    }
+
+    /// Reads a byte into the current cell, following the [`EofBehaviour`].
+    fn input(&mut self) -> Result<(), BraincluckError> {
+        let eof = self.new_label("input_eof");
+        let done = format!("{eof}_done");
+        self.call("call", Routine::Getc)?;
+        self.line("test rax, rax")?;
+        self.line(&format!("js {eof}"))?;
+        self.line(&format!("mov {}, {}", self.cell, self.rax))?;
+        self.line(&format!("jmp {done}"))?;
+        self.label(&eof)?;
+        match self.config.eof_behaviour {
+            EofBehaviour::Zero => self.line(&format!("mov {}, 0", self.cell))?,
+            EofBehaviour::NegativeOne => self.line(&format!("mov {}, {}", self.cell, self.rax))?,
+            EofBehaviour::Unchanged => {}
+            EofBehaviour::Error => self.call("jmp", Routine::UnexpectedEof)?,
+        }
+        self.label(&done)
+    }
+
+    /// Writes `message` to stderr.
+    fn write_message(&mut self, message: Message) -> Result<(), BraincluckError> {
+        let label = message.label();
+        match self.syntax {
+            Syntax::Gas => self.line(&format!("lea rsi, [rip + {label}]"))?,
+            Syntax::Nasm => self.line(&format!("lea rsi, [rel {label}]"))?,
+        }
+        self.line(&format!("mov edx, {}", message.text().len()))?;
+        self.call("call", Routine::WriteError)
+    }
+
+    /// Flushes the output, writes `message`, and exits with a code of 1.
+    fn fail(&mut self, message: Message) -> Result<(), BraincluckError> {
+        self.call("call", Routine::Flush)?;
+        self.write_message(message)?;
+        self.exit_failure()
+    }
+
+    fn exit_failure(&mut self) -> Result<(), BraincluckError> {
+        self.line_with("mov eax, 231", "exit_group")?;
+        self.line("mov edi, 1")?;
+        self.line("syscall")
+    }
+
+    /// Writes the shared routines that the program calls or jumps to.
+    fn routines(&mut self) -> Result<(), BraincluckError> {
+        for routine in Routine::ALL {
+            writeln!(self.code)?;
+            if let Some(description) = routine.description() {
+                self.comment(description)?;
+            }
+            self.label(routine.label())?;
+            self.routine(routine)?;
+        }
+        Ok(())
+    }
+
+    fn routine(&mut self, routine: Routine) -> Result<(), BraincluckError> {
+        match routine {
+            Routine::Flush => {
+                self.line("test rbp, rbp")?;
+                self.line("je flush_done")?;
+                self.line("mov rsi, rbx")?;
+                self.label("flush_again")?;
+                self.line_with("mov eax, 1", "write")?;
+                self.line_with("mov edi, 1", "stdout")?;
+                self.line("mov rdx, rbp")?;
+                self.line("syscall")?;
+                self.line("test rax, rax")?;
+                self.line("jle flush_failed")?;
+                self.line("add rsi, rax")?;
+                self.line("sub rbp, rax")?;
+                self.line("jne flush_again")?;
+                self.label("flush_done")?;
+                self.line("ret")?;
+
+                // There's nowhere left to report a broken stdout.
+                self.label("flush_failed")?;
+                self.exit_failure()
+            }
+            Routine::Putc => {
+                self.line("mov [rbx + rbp], al")?;
+                self.line("inc rbp")?;
+                self.line(&format!("cmp rbp, {BUFFER_SIZE}"))?;
+                self.call("je", Routine::Flush)?;
+                self.line("ret")
+            }
+            Routine::Getc => {
+                self.call("call", Routine::Flush)?;
+                self.line_with("push rax", "to make room for the byte")?;
+                self.line_with("xor eax, eax", "read")?;
+                self.line_with("xor edi, edi", "stdin")?;
+                self.line("mov rsi, rsp")?;
+                self.line("mov edx, 1")?;
+                self.line("syscall")?;
+                self.line("cmp rax, 1")?;
+                self.line("jne getc_eof")?;
+                self.line(match self.syntax {
+                    Syntax::Gas => "movzx eax, byte ptr [rsp]",
+                    Syntax::Nasm => "movzx eax, byte [rsp]",
+                })?;
+                self.line("pop rdx")?;
+                self.line("ret")?;
+                self.label("getc_eof")?;
+                self.line("mov rax, -1")?;
+                self.line("pop rdx")?;
+                self.line("ret")
+            }
+            Routine::WriteError => {
+                self.line_with("mov eax, 1", "write")?;
+                self.line_with("mov edi, 2", "stderr")?;
+                self.line("syscall")?;
+                self.line("ret")
+            }
+            Routine::PrintUnsigned => {
+                self.line("sub rsp, 32")?;
+                self.line("lea rsi, [rsp + 32]")?;
+                self.line("mov r10d, 10")?;
+                self.label("print_digit")?;
+                self.line("xor edx, edx")?;
+                self.line("div r10")?;
+                self.line_with("add dl, 48", "'0'")?;
+                self.line("dec rsi")?;
+                self.line("mov [rsi], dl")?;
+                self.line("test rax, rax")?;
+                self.line("jne print_digit")?;
+                self.line("lea rdx, [rsp + 32]")?;
+                self.line("sub rdx, rsi")?;
+                self.call("call", Routine::WriteError)?;
+                self.line("add rsp, 32")?;
+                self.line("ret")
+            }
+            Routine::PrintSigned => {
+                self.line("test rax, rax")?;
+                self.call("jns", Routine::PrintUnsigned)?;
+                self.line("push rax")?;
+                self.write_message(Message::Minus)?;
+                self.line("pop rax")?;
+                self.line("neg rax")?;
+                self.call("jmp", Routine::PrintUnsigned)
+            }
+            Routine::UnexpectedEof => self.fail(Message::UnexpectedEof),
+            Routine::Conversion => self.fail(Message::Conversion),
+            Routine::OutOfMemory => self.fail(Message::OutOfMemory),
+            Routine::OutOfBounds => {
+                // The first cell past the end, see the [module documentation](super).
+                self.line("mov r8, r14")?;
+                self.line("test rax, rax")?;
+                self.line("jns out_of_bounds_report")?;
+                self.line("mov r8, -1")?;
+                self.label("out_of_bounds_report")?;
+                self.call("call", Routine::Flush)?;
+                self.write_message(Message::OutOfBounds)?;
+                self.line("mov rax, r8")?;
+                self.call("call", Routine::PrintSigned)?;
+                self.write_message(Message::OutOfBoundsLength)?;
+                self.line("mov rax, r14")?;
+                self.call("call", Routine::PrintUnsigned)?;
+                self.write_message(Message::OutOfBoundsCells)?;
+                self.exit_failure()
+            }
+            Routine::StepLimitExceeded => {
+                self.line("dec r15")?;
+                self.line("mov r8, r13")?;
+                if self.config.tape_mode == TapeMode::Growable {
+                    self.line("sub r8, r14")?;
+                }
+                self.call("call", Routine::Flush)?;
+                self.write_message(Message::StepLimit)?;
+                self.line("mov rax, r15")?;
+                self.call("call", Routine::PrintUnsigned)?;
+                self.write_message(Message::StepLimitPosition)?;
+                self.line("mov rax, r8")?;
+                self.call("call", Routine::PrintUnsigned)?;
+                self.write_message(Message::StepLimitEnd)?;
+                self.exit_failure()
+            }
+        }
+    }
+
+    /// Writes the messages, with their escapes for the assembler.
+    fn messages(&mut self) -> Result<(), BraincluckError> {
+        writeln!(self.code)?;
+        match self.syntax {
+            Syntax::Gas => writeln!(self.code, ".section .rodata")?,
+            Syntax::Nasm => writeln!(self.code, "section .rodata")?,
+        }
+        for message in Message::ALL {
+            let label = message.label();
+            let escaped = message.text().replace('\n', "\\n");
+            match self.syntax {
+                Syntax::Gas => writeln!(self.code, "{label}: .ascii \"{escaped}\"")?,
+                Syntax::Nasm => writeln!(self.code, "{label}: db `{escaped}`")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64", target_os = "linux"))]
+mod tests {
+    use std::{
+        env, fs,
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    use crate::{bf_parse, ir::Program, Config};
+
+    use super::{
+        super::tests::{check_generator, exited, missing_tools, Run},
+        generate, Syntax,
+    };
+
+    /// Returns whether `tool` can be run.
+    fn available(tool: &str) -> bool {
+        Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    fn listing(program: &str, config: &Config, syntax: Syntax) -> String {
+        let commands = bf_parse(program).expect("parsing returned an error");
+        generate(&Program::new(&commands), config, program, syntax)
+            .expect("generating should succeed")
+    }
+
+    /// Assembles and links `program`, and runs it with `input`.
+    fn run_assembled(
+        syntax: Syntax,
+        name: &str,
+        program: &str,
+        config: &Config,
+        input: &[u8],
+    ) -> Run {
+        let dir: PathBuf = env::temp_dir().join(format!(
+            "braincluck-asm-{}-{syntax:?}-{name}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("creating a directory should work");
+        let source = dir.join("program.s");
+        let object = dir.join("program.o");
+        let binary = dir.join("program");
+        fs::write(&source, listing(program, config, syntax))
+            .expect("writing the source should work");
+
+        let mut assembler = match syntax {
+            Syntax::Gas => Command::new("as"),
+            Syntax::Nasm => {
+                let mut nasm = Command::new("nasm");
+                nasm.args(["-f", "elf64"]);
+                nasm
+            }
+        };
+        let status = assembler
+            .arg("-o")
+            .arg(&object)
+            .arg(&source)
+            .status()
+            .expect("running the assembler should work");
+        assert!(status.success(), "{name} should assemble");
+        let status = Command::new("ld")
+            .arg("-o")
+            .arg(&binary)
+            .arg(&object)
+            .status()
+            .expect("running the linker should work");
+        assert!(status.success(), "{name} should link");
+
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("running the program should work");
+        child
+            .stdin
+            .take()
+            .expect("stdin should be piped")
+            .write_all(input)
+            .expect("writing input should work");
+        let output = child
+            .wait_with_output()
+            .expect("running the program should work");
+        fs::remove_dir_all(&dir).expect("removing a directory should work");
+
+        exited(
+            output.status.code(),
+            output.stdout,
+            &String::from_utf8_lossy(&output.stderr),
+        )
+    }
+
+    /// Checks that programs assembled from `syntax` behave the same as the interpreter.
+    fn compare(syntax: Syntax) {
+        check_generator(|name, program, config, input| {
+            run_assembled(syntax, name, program, config, input)
+        });
+    }
+
+    #[test]
+    fn gas() {
+        if !available("as") || !available("ld") {
+            return missing_tools("no GNU assembler or linker found");
+        }
+        compare(Syntax::Gas);
+    }
+
+    #[test]
+    fn nasm() {
+        if !available("nasm") || !available("ld") {
+            return missing_tools("no NASM or linker found");
+        }
+        compare(Syntax::Nasm);
+    }
+
+    /// Each block should be commented with the commands it came from, without the comments in the source.
+    #[test]
+    fn annotations() {
+        let gas = listing("++ add two\n[->+<]>.", &Config::default(), Syntax::Gas);
+        for comment in [
+            "# line 1, column 1: ++",
+            "# line 2, column 1: [->+<]",
+            "# line 2, column 7: >",
+            "# line 2, column 8: .",
+        ] {
+            assert!(gas.contains(comment), "{comment} should be in:\n{gas}");
+        }
+        assert!(!gas.contains("add two"), "{gas}");
+
+        let nasm = listing("+[-]", &Config::default(), Syntax::Nasm);
+        assert!(nasm.contains("; line 1, column 2: [-]"), "{nasm}");
+        assert!(nasm.contains("mov byte [r12 + r13*1], 0"), "{nasm}");
+    }
+}
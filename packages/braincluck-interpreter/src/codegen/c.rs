@@ -0,0 +1,345 @@
+use std::fmt::Write;
+
+use crate::{
+    ir::{Op, Program},
+    BraincluckError, CellWidth, Config, EofBehaviour, OutputMode, TapeMode,
+};
+
+use super::{require_wrapping, INITIAL_LENGTH};
+
+const PRELUDE: &str = r#"#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static cell *tape;
+static size_t length;
+static size_t p;
+
+static void *allocate(void *memory, size_t cells) {
+    memory = realloc(memory, cells * sizeof(cell));
+    if (memory == NULL) {
+        fflush(stdout);
+        fprintf(stderr, "error: out of memory\n");
+        exit(1);
+    }
+    return memory;
+}
+"#;
+
+const MOVE_GROWABLE: &str = r#"
+static void move_by(ptrdiff_t offset) {
+    if (offset < 0 && (size_t)-offset > p) {
+        size_t extra = (size_t)-offset - p;
+        tape = allocate(tape, length + extra);
+        memmove(tape + extra, tape, length * sizeof(cell));
+        memset(tape, 0, extra * sizeof(cell));
+        length += extra;
+        p = 0;
+    } else {
+        p += (size_t)offset;
+        if (p >= length) {
+            size_t grown = length * 2 > p ? length * 2 : p + 1;
+            tape = allocate(tape, grown);
+            memset(tape + length, 0, (grown - length) * sizeof(cell));
+            length = grown;
+        }
+    }
+}
+"#;
+
+/// Reports the first cell past the end of the tape, as the [module documentation](super) describes.
+const MOVE_BOUNDED: &str = r#"
+static void move_by(ptrdiff_t offset) {
+    ptrdiff_t position = (ptrdiff_t)p + offset;
+    if (position < 0 || (size_t)position >= length) {
+        fflush(stdout);
+        fprintf(stderr, "error: pointer moved to cell %td, outside of a tape with %zu cells\n",
+                position < 0 ? (ptrdiff_t)-1 : (ptrdiff_t)length, length);
+        exit(1);
+    }
+    p = (size_t)position;
+}
+"#;
+
+const MOVE_CIRCULAR: &str = r#"
+static void move_by(ptrdiff_t offset) {
+    ptrdiff_t position = ((ptrdiff_t)p + offset) % (ptrdiff_t)length;
+    p = (size_t)(position < 0 ? position + (ptrdiff_t)length : position);
+}
+"#;
+
+const OUTPUT_BYTE: &str = r#"
+static void output(void) {
+    putchar((unsigned char)tape[p]);
+}
+"#;
+
+const OUTPUT_TEXT: &str = r#"
+static void output(void) {
+    int64_t value = VALUE;
+    if (value < 0 || value > 255) {
+        fflush(stdout);
+        fprintf(stderr, "error: out of range integral type conversion attempted\n");
+        exit(1);
+    }
+    if (value < 0x80) {
+        putchar((int)value);
+    } else {
+        putchar((int)(0xc0 | (value >> 6)));
+        putchar((int)(0x80 | (value & 0x3f)));
+    }
+}
+"#;
+
+const STEP: &str = r#"
+static uint64_t steps;
+
+static void step(void) {
+    if (steps >= STEP_LIMIT) {
+        fflush(stdout);
+        fprintf(stderr, "error: stopped after %llu steps at cell %zu\n", (unsigned long long)steps, p);
+        exit(1);
+    }
+    steps++;
+}
+"#;
+
+/// Compiles `program` into a standalone C99 program that reads from stdin and writes to stdout.
+///
+/// The cells are stored as unsigned integers of the width set in `config`, so all arithmetic wraps around
+/// without any undefined behaviour. Checked arithmetic is not supported.
+pub fn generate(program: &Program, config: &Config) -> Result<String, BraincluckError> {
+    require_wrapping(config)?;
+
+    let (cell, value) = match config.cell_width {
+        CellWidth::I8 => ("uint8_t", "(int64_t)(int8_t)tape[p]"),
+        CellWidth::U8 => ("uint8_t", "(int64_t)tape[p]"),
+        CellWidth::U16 => ("uint16_t", "(int64_t)tape[p]"),
+        CellWidth::U32 => ("uint32_t", "(int64_t)tape[p]"),
+        CellWidth::I64 => ("uint64_t", "(int64_t)tape[p]"),
+    };
+    let (length, move_by) = match config.tape_mode {
+        TapeMode::Growable => (INITIAL_LENGTH, MOVE_GROWABLE),
+        TapeMode::Bounded(length) => (length, MOVE_BOUNDED),
+        TapeMode::Circular(length) => (length, MOVE_CIRCULAR),
+    };
+
+    let mut code = String::new();
+    writeln!(code, "/* Generated by braincluck. */")?;
+    code.push_str(&PRELUDE.replace(
+        "static cell *tape;",
+        &format!("typedef {cell} cell;\n\nstatic cell *tape;"),
+    ));
+    // Unused static functions are warned about, so only the ones the program needs are written.
+    let uses = |f: fn(&Op) -> bool| program.ops().iter().any(f);
+    if uses(|op| matches!(op, Op::Move(_) | Op::Scan(_) | Op::MulAdd { .. })) {
+        code.push_str(move_by);
+    }
+    if uses(|op| matches!(op, Op::Output)) {
+        match config.output_mode {
+            OutputMode::Byte => code.push_str(OUTPUT_BYTE),
+            OutputMode::Text => code.push_str(&OUTPUT_TEXT.replace("VALUE", value)),
+        }
+    }
+    if uses(|op| matches!(op, Op::Input)) {
+        write_input(&mut code, config.eof_behaviour)?;
+    }
+
+    if let Some(step_limit) = config.step_limit {
+        code.push_str(&STEP.replace("STEP_LIMIT", &format!("UINT64_C({step_limit})")));
+    }
+
+    writeln!(code)?;
+    writeln!(code, "int main(void) {{")?;
+    writeln!(code, "    length = {length};")?;
+    writeln!(code, "    tape = allocate(NULL, length);")?;
+    writeln!(code, "    memset(tape, 0, length * sizeof(cell));")?;
+    writeln!(code)?;
+
+    let step = if config.step_limit.is_some() {
+        "step(); "
+    } else {
+        ""
+    };
+    let mut depth = 1;
+    for op in program.ops() {
+        if let Op::LoopEnd(_) = op {
+            depth -= 1;
+        }
+        let indent = "    ".repeat(depth);
+
+        match *op {
+            Op::Add(delta) => writeln!(code, "{indent}{step}{};", add(delta))?,
+            Op::Move(offset) => writeln!(code, "{indent}{step}move_by({offset});")?,
+            Op::Output => writeln!(code, "{indent}{step}output();")?,
+            Op::Input => writeln!(code, "{indent}{step}input();")?,
+            // The end of a loop counts a step every time it checks the cell, but the start only counts one.
+            Op::LoopStart(_) => {
+                writeln!(code, "{indent}{step}while (tape[p]) {{")?;
+                depth += 1;
+            }
+            Op::LoopEnd(_) => {
+                if !step.is_empty() {
+                    writeln!(code, "{indent}    step();")?;
+                }
+                writeln!(code, "{indent}}}")?;
+            }
+            Op::Clear(_) => writeln!(code, "{indent}{step}tape[p] = 0;")?,
+            Op::Scan(offset) => writeln!(
+                code,
+                "{indent}{step}while (tape[p]) {{ {step}move_by({offset}); }}"
+            )?,
+            Op::MulAdd { offset, factor } => writeln!(
+                code,
+                "{indent}{step}if (tape[p]) {{ cell value = tape[p]; move_by({offset}); tape[p] = (cell)(tape[p] + (uint64_t)value * {}); move_by({}); }}",
+                u64_literal(factor),
+                -offset
+            )?,
+        }
+    }
+
+    writeln!(code)?;
+    writeln!(code, "    return 0;")?;
+    writeln!(code, "}}")?;
+
+    Ok(code)
+}
+
+/// Writes the function that runs `,`.
+fn write_input(code: &mut String, eof_behaviour: EofBehaviour) -> Result<(), BraincluckError> {
+    writeln!(code)?;
+    writeln!(code, "static void input(void) {{")?;
+    writeln!(code, "    int c = getchar();")?;
+    writeln!(code, "    if (c != EOF) {{")?;
+    writeln!(code, "        tape[p] = (cell)c;")?;
+    match eof_behaviour {
+        EofBehaviour::Zero => {
+            writeln!(code, "    }} else {{")?;
+            writeln!(code, "        tape[p] = 0;")?;
+        }
+        EofBehaviour::NegativeOne => {
+            writeln!(code, "    }} else {{")?;
+            writeln!(code, "        tape[p] = (cell)~(cell)0;")?;
+        }
+        EofBehaviour::Unchanged => {}
+        EofBehaviour::Error => {
+            writeln!(code, "    }} else {{")?;
+            writeln!(code, "        fflush(stdout);")?;
+            writeln!(
+                code,
+                "        fprintf(stderr, \"error: tried to read input past the end of the input\\n\");"
+            )?;
+            writeln!(code, "        exit(1);")?;
+        }
+    }
+    writeln!(code, "    }}")?;
+    writeln!(code, "}}")?;
+
+    Ok(())
+}
+
+/// Returns a C statement that adds `delta` to the current cell, wrapping around.
+fn add(delta: i64) -> String {
+    if delta < 0 {
+        format!("tape[p] -= (cell)UINT64_C({})", delta.unsigned_abs())
+    } else {
+        format!("tape[p] += (cell)UINT64_C({delta})")
+    }
+}
+
+/// Returns `value` as an unsigned C literal, wrapping negative values around.
+fn u64_literal(value: i64) -> String {
+    format!("UINT64_C({})", value as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    use crate::{bf_parse, ir::Program, Config};
+
+    use super::{
+        super::tests::{check_generator, exited, missing_tools, Run},
+        generate,
+    };
+
+    /// Returns a C compiler to test with, if there is one.
+    fn compiler() -> Option<String> {
+        let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+        Command::new(&compiler)
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|_| compiler)
+    }
+
+    /// Compiles `program` to C, builds it, and runs it with `input`.
+    fn run_compiled(
+        compiler: &str,
+        name: &str,
+        program: &str,
+        config: &Config,
+        input: &[u8],
+    ) -> Run {
+        let commands = bf_parse(program).expect("parsing returned an error");
+        let code = generate(&Program::new(&commands), config).expect("generating should succeed");
+
+        let dir: PathBuf =
+            env::temp_dir().join(format!("braincluck-c-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creating a directory should work");
+        let source = dir.join("program.c");
+        let binary = dir.join("program");
+        fs::write(&source, code).expect("writing the source should work");
+
+        let status = Command::new(compiler)
+            .args(["-std=c99", "-O1", "-Wall", "-Werror", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .status()
+            .expect("running the compiler should work");
+        assert!(status.success(), "{name} should compile");
+
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("running the program should work");
+        child
+            .stdin
+            .take()
+            .expect("stdin should be piped")
+            .write_all(input)
+            .expect("writing input should work");
+        let output = child
+            .wait_with_output()
+            .expect("running the program should work");
+        fs::remove_dir_all(&dir).expect("removing a directory should work");
+
+        exited(
+            output.status.code(),
+            output.stdout,
+            &String::from_utf8_lossy(&output.stderr),
+        )
+    }
+
+    /// Compiled programs should behave the same as the interpreter.
+    #[test]
+    fn matches_interpreter() {
+        let Some(compiler) = compiler() else {
+            return missing_tools("no C compiler found");
+        };
+
+        check_generator(|name, program, config, input| {
+            run_compiled(&compiler, name, program, config, input)
+        });
+    }
+}
@@ -0,0 +1,593 @@
+//! Compiles programs straight to static x86-64 Linux executables, without needing a C compiler or assembler.
+//!
+//! The executable only uses raw system calls: `mmap` for the tape and output buffer, `read` and `write` for
+//! I/O, and `exit_group`. Output is buffered, and flushed before reading input and before exiting.
+
+use crate::{
+    ir::{Op, Program},
+    x86::{Assembler, CALL, JA, JAE, JB, JE, JGE, JMP, JNE, JNS, JS, R13, R8, RAX},
+    BraincluckError, CellWidth, Config, EofBehaviour, OutputMode, TapeMode,
+};
+
+use super::{
+    linux::{self, Message, Routine, BUFFER_SIZE, GROWABLE_SIZE},
+    require_wrapping,
+};
+
+/// Where the executable is loaded in memory.
+const BASE_ADDRESS: u64 = 0x400000;
+
+/// The size of the ELF header and the single program header before the code.
+const HEADERS_SIZE: u64 = 64 + 56;
+
+/// Compiles `program` into a static x86-64 Linux executable that reads from stdin and writes to stdout. Errors
+/// are written to stderr with the same messages as the interpreter, and exit with a code of 1.
+///
+/// Growable tapes can grow by up to 512 MiB in either direction. Checked arithmetic is not supported.
+pub fn generate(program: &Program, config: &Config) -> Result<Vec<u8>, BraincluckError> {
+    require_wrapping(config)?;
+
+    let width = linux::width(config);
+    let mut compiler = Compiler {
+        asm: Assembler::new(width),
+        config: *config,
+        width,
+        calls: vec![],
+        messages: vec![],
+    };
+    compiler.program(program);
+    let code = compiler.finish();
+
+    let size = HEADERS_SIZE + code.len() as u64;
+    let mut elf = Vec::with_capacity(size as usize);
+
+    // The ELF header, for a 64 bit little endian executable.
+    elf.extend(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+    elf.extend(2_u16.to_le_bytes()); // e_type: an executable
+    elf.extend(0x3e_u16.to_le_bytes()); // e_machine: x86-64
+    elf.extend(1_u32.to_le_bytes()); // e_version
+    elf.extend((BASE_ADDRESS + HEADERS_SIZE).to_le_bytes()); // e_entry
+    elf.extend(64_u64.to_le_bytes()); // e_phoff
+    elf.extend(0_u64.to_le_bytes()); // e_shoff
+    elf.extend(0_u32.to_le_bytes()); // e_flags
+    elf.extend(64_u16.to_le_bytes()); // e_ehsize
+    elf.extend(56_u16.to_le_bytes()); // e_phentsize
+    elf.extend(1_u16.to_le_bytes()); // e_phnum
+    elf.extend(64_u16.to_le_bytes()); // e_shentsize
+    elf.extend(0_u16.to_le_bytes()); // e_shnum
+    elf.extend(0_u16.to_le_bytes()); // e_shstrndx
+
+    // A single readable and executable segment with the whole file.
+    elf.extend(1_u32.to_le_bytes()); // p_type: loadable
+    elf.extend(5_u32.to_le_bytes()); // p_flags: read and execute
+    elf.extend(0_u64.to_le_bytes()); // p_offset
+    elf.extend(BASE_ADDRESS.to_le_bytes()); // p_vaddr
+    elf.extend(BASE_ADDRESS.to_le_bytes()); // p_paddr
+    elf.extend(size.to_le_bytes()); // p_filesz
+    elf.extend(size.to_le_bytes()); // p_memsz
+    elf.extend(0x1000_u64.to_le_bytes()); // p_align
+
+    elf.extend(code);
+    Ok(elf)
+}
+
+/// Keeps track of the calls and references to messages that need patching while compiling. The registers are
+/// used as described in [`linux`].
+struct Compiler {
+    asm: Assembler,
+    config: Config,
+    width: u32,
+    /// Calls and jumps to routines.
+    calls: Vec<(usize, Routine)>,
+    /// References to messages.
+    messages: Vec<(usize, Message)>,
+}
+
+impl Compiler {
+    fn call(&mut self, opcode: &[u8], routine: Routine) {
+        let jump = self.asm.jump(opcode);
+        self.calls.push((jump, routine));
+    }
+
+    /// Writes a message to stderr.
+    fn write_error(&mut self, message: Message) {
+        self.asm.emit(&[0x48, 0x8d, 0x35]); // lea rsi, [rip + message]
+        let reference = self.asm.jump(&[]);
+        self.messages.push((reference, message));
+        self.asm.emit(&[0xba]); // mov edx, message.len()
+        self.asm.emit(&(message.text().len() as u32).to_le_bytes());
+        self.call(CALL, Routine::WriteError);
+    }
+
+    fn exit(&mut self, code: u8) {
+        self.asm.emit(&[0xb8, 231, 0, 0, 0]); // mov eax, SYS_exit_group
+        self.asm.emit(&[0xbf, code, 0, 0, 0]); // mov edi, code
+        self.asm.emit(&[0x0f, 0x05]); // syscall
+    }
+
+    fn program(&mut self, program: &Program) {
+        let (size, start, r14) = linux::tape(&self.config);
+
+        // Map the output buffer and the tape, which start out zeroed.
+        let asm = &mut self.asm;
+        asm.emit(&[0xb8, 9, 0, 0, 0]); // mov eax, SYS_mmap
+        asm.emit(&[0x31, 0xff]); // xor edi, edi
+        asm.emit(&[0x48, 0xbe]); // mov rsi, size
+        asm.emit(&(BUFFER_SIZE as u64 + size).to_le_bytes());
+        asm.emit(&[0xba, 3, 0, 0, 0]); // mov edx, PROT_READ | PROT_WRITE
+        asm.emit(&[0x41, 0xba, 0x22, 0x40, 0, 0]); // mov r10d, MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE
+        asm.emit(&[0x49, 0xc7, 0xc0, 0xff, 0xff, 0xff, 0xff]); // mov r8, -1
+        asm.emit(&[0x45, 0x31, 0xc9]); // xor r9d, r9d
+        asm.emit(&[0x0f, 0x05]); // syscall
+        asm.emit(&[0x48, 0x3d, 0x01, 0xf0, 0xff, 0xff]); // cmp rax, -4095
+        self.call(JAE, Routine::OutOfMemory);
+
+        let asm = &mut self.asm;
+        asm.emit(&[0x48, 0x89, 0xc3]); // mov rbx, rax
+        asm.emit(&[0x4c, 0x8d, 0xa0]); // lea r12, [rax + BUFFER_SIZE]
+        asm.emit(&BUFFER_SIZE.to_le_bytes());
+        asm.emit(&[0x31, 0xed]); // xor ebp, ebp
+        asm.emit(&[0x49, 0xbd]); // mov r13, start
+        asm.emit(&start.to_le_bytes());
+        asm.emit(&[0x49, 0xbe]); // mov r14, length or start
+        asm.emit(&r14.to_le_bytes());
+        asm.emit(&[0x45, 0x31, 0xff]); // xor r15d, r15d
+        if let Some(step_limit) = self.config.step_limit {
+            asm.emit(&[0x49, 0xb9]); // mov r9, step_limit
+            asm.emit(&step_limit.to_le_bytes());
+        }
+
+        let mut loops = vec![];
+        for op in program.ops() {
+            self.step();
+            match *op {
+                Op::Add(delta) => self.asm.add_cell(delta),
+                Op::Move(offset) => self.move_by(offset),
+                Op::Output => self.output(),
+                Op::Input => self.input(),
+                Op::LoopStart(_) => {
+                    self.asm.compare_cell_to_zero();
+                    let end = self.asm.jump(JE);
+                    loops.push((self.asm.here(), end));
+                }
+                Op::LoopEnd(_) => {
+                    let (body, end) = loops.pop().expect("loops should be balanced");
+                    self.asm.compare_cell_to_zero();
+                    self.asm.jump_to(JNE, body);
+                    self.asm.patch_here(end);
+                }
+                Op::Clear(_) => self.asm.clear_cell(),
+                Op::Scan(offset) => {
+                    let start = self.asm.here();
+                    self.asm.compare_cell_to_zero();
+                    let end = self.asm.jump(JE);
+                    self.step();
+                    self.move_by(offset);
+                    self.asm.jump_to(JMP, start);
+                    self.asm.patch_here(end);
+                }
+                Op::MulAdd { offset, factor } => {
+                    self.asm.compare_cell_to_zero();
+                    let zero = self.asm.jump(JE);
+                    self.asm.load_cell(RAX);
+                    self.asm.multiply(factor);
+                    self.asm.emit(&[0x49, 0x89, 0xc0]); // mov r8, rax
+                    self.move_by(offset);
+                    self.asm.cell(0x00, 0x01, R8, R13); // add [cell], r8
+                    self.move_by(-offset);
+                    self.asm.patch_here(zero);
+                }
+            }
+        }
+
+        self.call(CALL, Routine::Flush);
+        self.exit(0);
+    }
+
+    /// Counts a step, stopping if this goes over the step limit.
+    fn step(&mut self) {
+        if self.config.step_limit.is_some() {
+            self.asm.emit(&[0x49, 0xff, 0xc7]); // inc r15
+            self.asm.emit(&[0x4d, 0x39, 0xcf]); // cmp r15, r9
+            self.call(JA, Routine::StepLimitExceeded);
+        }
+    }
+
+    /// Moves the pointer, following the [`TapeMode`]. Clobbers rax and rdx.
+    fn move_by(&mut self, offset: isize) {
+        let asm = &mut self.asm;
+        match i32::try_from(offset) {
+            Ok(displacement) => {
+                asm.emit(&[0x49, 0x8d, 0x85]); // lea rax, [r13 + offset]
+                asm.emit(&displacement.to_le_bytes());
+            }
+            Err(_) => {
+                asm.emit(&[0x48, 0xb8]); // mov rax, offset
+                asm.emit(&(offset as i64).to_le_bytes());
+                asm.emit(&[0x4c, 0x01, 0xe8]); // add rax, r13
+            }
+        }
+
+        match self.config.tape_mode {
+            TapeMode::Growable if offset > 0 => {
+                let cells = GROWABLE_SIZE / self.width as u64;
+                asm.emit(&[0x48, 0x3d]); // cmp rax, cells
+                asm.emit(&(cells as u32).to_le_bytes());
+                self.call(JAE, Routine::OutOfMemory);
+            }
+            TapeMode::Growable => {
+                asm.emit(&[0x4c, 0x39, 0xf0]); // cmp rax, r14
+                let inside = asm.jump(JGE);
+                asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+                self.call(JS, Routine::OutOfMemory);
+                self.asm.emit(&[0x49, 0x89, 0xc6]); // mov r14, rax
+                self.asm.patch_here(inside);
+            }
+            TapeMode::Bounded(_) => {
+                asm.emit(&[0x4c, 0x39, 0xf0]); // cmp rax, r14
+                self.call(JAE, Routine::OutOfBounds);
+            }
+            TapeMode::Circular(_) => {
+                asm.emit(&[0x4c, 0x39, 0xf0]); // cmp rax, r14
+                let inside = asm.jump(JB);
+                asm.emit(&[0x48, 0x99]); // cqo
+                asm.emit(&[0x49, 0xf7, 0xfe]); // idiv r14
+                asm.emit(&[0x48, 0x89, 0xd0]); // mov rax, rdx
+                asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+                let positive = asm.jump(JNS);
+                asm.emit(&[0x4c, 0x01, 0xf0]); // add rax, r14
+                asm.patch_here(positive);
+                asm.patch_here(inside);
+            }
+        }
+
+        self.asm.emit(&[0x49, 0x89, 0xc5]); // mov r13, rax
+    }
+
+    /// Writes the current cell, following the [`OutputMode`].
+    fn output(&mut self) {
+        match self.config.output_mode {
+            OutputMode::Byte => {
+                self.asm.load_cell(RAX);
+                self.call(CALL, Routine::Putc);
+            }
+            // Only 0 to 255 can be written, see the [module documentation](super).
+            OutputMode::Text => {
+                self.load_value();
+                self.asm.emit(&[0x48, 0x3d, 0xff, 0, 0, 0]); // cmp rax, 255
+                self.call(JA, Routine::Conversion);
+                self.asm.emit(&[0x3d, 0x80, 0, 0, 0]); // cmp eax, 0x80
+                let ascii = self.asm.jump(JB);
+                self.asm.emit(&[0x49, 0x89, 0xc0]); // mov r8, rax
+                self.asm.emit(&[0xc1, 0xe8, 6]); // shr eax, 6
+                self.asm.emit(&[0x0d, 0xc0, 0, 0, 0]); // or eax, 0xc0
+                self.call(CALL, Routine::Putc);
+                self.asm.emit(&[0x4c, 0x89, 0xc0]); // mov rax, r8
+                self.asm.emit(&[0x83, 0xe0, 0x3f]); // and eax, 0x3f
+                self.asm.emit(&[0x0d, 0x80, 0, 0, 0]); // or eax, 0x80
+                self.asm.patch_here(ascii);
+                self.call(CALL, Routine::Putc);
+            }
+        }
+    }
+
+    /// Loads the current cell into rax as a 64 bit value, sign extended for signed cells.
+    fn load_value(&mut self) {
+        let (rex, opcode): (u8, &[u8]) = match (self.config.cell_width, self.width) {
+            (CellWidth::I8, _) => (0x4b, &[0x0f, 0xbe]), // movsx rax, byte [cell]
+            (_, 1) => (0x43, &[0x0f, 0xb6]),             // movzx eax, byte [cell]
+            (_, 2) => (0x43, &[0x0f, 0xb7]),             // movzx eax, word [cell]
+            (_, 4) => (0x43, &[0x8b]),                   // mov eax, dword [cell]
+            _ => (0x4b, &[0x8b]),                        // mov rax, qword [cell]
+        };
+        let sib = (self.width.trailing_zeros() as u8) << 6 | (R13 & 7) << 3 | 0b100;
+        self.asm.emit(&[rex]);
+        self.asm.emit(opcode);
+        self.asm.emit(&[0x04, sib]);
+    }
+
+    /// Reads a byte into the current cell, following the [`EofBehaviour`].
+    fn input(&mut self) {
+        self.call(CALL, Routine::Getc);
+        self.asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+        let eof = self.asm.jump(JS);
+        self.asm.store_cell(RAX);
+        let done = self.asm.jump(JMP);
+        self.asm.patch_here(eof);
+        match self.config.eof_behaviour {
+            EofBehaviour::Zero => self.asm.clear_cell(),
+            EofBehaviour::NegativeOne => self.asm.store_cell(RAX),
+            EofBehaviour::Unchanged => {}
+            EofBehaviour::Error => self.call(JMP, Routine::UnexpectedEof),
+        }
+        self.asm.patch_here(done);
+    }
+
+    /// Emits the routines that were used, patches every call to them, and adds the messages at the end.
+    fn finish(mut self) -> Vec<u8> {
+        // Routines can call other routines, which are added to the end of the calls as they're emitted.
+        let mut addresses: Vec<(Routine, usize)> = vec![];
+        let mut call = 0;
+        while call < self.calls.len() {
+            let (_, routine) = self.calls[call];
+            if !addresses.iter().any(|&(emitted, _)| emitted == routine) {
+                addresses.push((routine, self.asm.here()));
+                self.routine(routine);
+            }
+            call += 1;
+        }
+
+        for &(jump, routine) in &self.calls {
+            let (_, address) = addresses
+                .iter()
+                .find(|(emitted, _)| *emitted == routine)
+                .expect("every routine should be emitted");
+            self.asm.patch(jump, *address);
+        }
+        for &(reference, message) in &self.messages {
+            let address = self.asm.here();
+            self.asm.patch(reference, address);
+            self.asm.emit(message.text().as_bytes());
+        }
+
+        self.asm.code
+    }
+
+    fn routine(&mut self, routine: Routine) {
+        match routine {
+            Routine::Flush => {
+                let asm = &mut self.asm;
+                asm.emit(&[0x48, 0x85, 0xed]); // test rbp, rbp
+                let empty = asm.jump(JE);
+                asm.emit(&[0x48, 0x89, 0xde]); // mov rsi, rbx
+                let again = asm.here();
+                asm.emit(&[0xb8, 1, 0, 0, 0]); // mov eax, SYS_write
+                asm.emit(&[0xbf, 1, 0, 0, 0]); // mov edi, 1
+                asm.emit(&[0x48, 0x89, 0xea]); // mov rdx, rbp
+                asm.emit(&[0x0f, 0x05]); // syscall
+                asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+                let failed = asm.jump(&[0x0f, 0x8e]); // jle
+                asm.emit(&[0x48, 0x01, 0xc6]); // add rsi, rax
+                asm.emit(&[0x48, 0x29, 0xc5]); // sub rbp, rax
+                asm.jump_to(JNE, again);
+                asm.patch_here(empty);
+                asm.emit(&[0xc3]); // ret
+
+                // There's nowhere left to report a broken stdout.
+                self.asm.patch_here(failed);
+                self.exit(1);
+            }
+            Routine::Putc => {
+                self.asm.emit(&[0x88, 0x04, 0x2b]); // mov [rbx + rbp], al
+                self.asm.emit(&[0x48, 0xff, 0xc5]); // inc rbp
+                self.asm.emit(&[0x48, 0x81, 0xfd]); // cmp rbp, BUFFER_SIZE
+                self.asm.emit(&BUFFER_SIZE.to_le_bytes());
+                self.call(JE, Routine::Flush);
+                self.asm.emit(&[0xc3]); // ret
+            }
+            Routine::Getc => {
+                // See [`Routine::description`] for why this flushes first.
+                self.call(CALL, Routine::Flush);
+                let asm = &mut self.asm;
+                asm.emit(&[0x50]); // push rax, to make room for the byte
+                asm.emit(&[0x31, 0xc0]); // xor eax, eax (SYS_read)
+                asm.emit(&[0x31, 0xff]); // xor edi, edi
+                asm.emit(&[0x48, 0x89, 0xe6]); // mov rsi, rsp
+                asm.emit(&[0xba, 1, 0, 0, 0]); // mov edx, 1
+                asm.emit(&[0x0f, 0x05]); // syscall
+                asm.emit(&[0x48, 0x83, 0xf8, 1]); // cmp rax, 1
+                let eof = asm.jump(JNE);
+                asm.emit(&[0x0f, 0xb6, 0x04, 0x24]); // movzx eax, byte [rsp]
+                asm.emit(&[0x5a]); // pop rdx
+                asm.emit(&[0xc3]); // ret
+                asm.patch_here(eof);
+                asm.emit(&[0x48, 0xc7, 0xc0, 0xff, 0xff, 0xff, 0xff]); // mov rax, -1
+                asm.emit(&[0x5a]); // pop rdx
+                asm.emit(&[0xc3]); // ret
+            }
+            Routine::WriteError => {
+                self.asm.emit(&[0xb8, 1, 0, 0, 0]); // mov eax, SYS_write
+                self.asm.emit(&[0xbf, 2, 0, 0, 0]); // mov edi, 2
+                self.asm.emit(&[0x0f, 0x05]); // syscall
+                self.asm.emit(&[0xc3]); // ret
+            }
+            Routine::PrintUnsigned => {
+                let asm = &mut self.asm;
+                asm.emit(&[0x48, 0x83, 0xec, 32]); // sub rsp, 32
+                asm.emit(&[0x48, 0x8d, 0x74, 0x24, 32]); // lea rsi, [rsp + 32]
+                asm.emit(&[0x41, 0xba, 10, 0, 0, 0]); // mov r10d, 10
+                let digit = asm.here();
+                asm.emit(&[0x31, 0xd2]); // xor edx, edx
+                asm.emit(&[0x49, 0xf7, 0xf2]); // div r10
+                asm.emit(&[0x80, 0xc2, b'0']); // add dl, '0'
+                asm.emit(&[0x48, 0xff, 0xce]); // dec rsi
+                asm.emit(&[0x88, 0x16]); // mov [rsi], dl
+                asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+                asm.jump_to(JNE, digit);
+                asm.emit(&[0x48, 0x8d, 0x54, 0x24, 32]); // lea rdx, [rsp + 32]
+                asm.emit(&[0x48, 0x29, 0xf2]); // sub rdx, rsi
+                self.call(CALL, Routine::WriteError);
+                self.asm.emit(&[0x48, 0x83, 0xc4, 32]); // add rsp, 32
+                self.asm.emit(&[0xc3]); // ret
+            }
+            Routine::PrintSigned => {
+                self.asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+                self.call(JNS, Routine::PrintUnsigned);
+                self.asm.emit(&[0x50]); // push rax
+                self.write_error(Message::Minus);
+                self.asm.emit(&[0x58]); // pop rax
+                self.asm.emit(&[0x48, 0xf7, 0xd8]); // neg rax
+                self.call(JMP, Routine::PrintUnsigned);
+            }
+            Routine::UnexpectedEof => self.fail(Message::UnexpectedEof),
+            Routine::Conversion => self.fail(Message::Conversion),
+            Routine::OutOfMemory => self.fail(Message::OutOfMemory),
+            Routine::OutOfBounds => {
+                // The first cell past the end, see the [module documentation](super).
+                self.asm.emit(&[0x4d, 0x89, 0xf0]); // mov r8, r14
+                self.asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+                let after = self.asm.jump(JNS);
+                self.asm.emit(&[0x49, 0xc7, 0xc0, 0xff, 0xff, 0xff, 0xff]); // mov r8, -1
+                self.asm.patch_here(after);
+                self.call(CALL, Routine::Flush);
+                self.write_error(Message::OutOfBounds);
+                self.asm.emit(&[0x4c, 0x89, 0xc0]); // mov rax, r8
+                self.call(CALL, Routine::PrintSigned);
+                self.write_error(Message::OutOfBoundsLength);
+                self.asm.emit(&[0x4c, 0x89, 0xf0]); // mov rax, r14
+                self.call(CALL, Routine::PrintUnsigned);
+                self.write_error(Message::OutOfBoundsCells);
+                self.exit(1);
+            }
+            Routine::StepLimitExceeded => {
+                // The step that went over the limit wasn't actually taken.
+                self.asm.emit(&[0x49, 0xff, 0xcf]); // dec r15
+                self.asm.emit(&[0x4d, 0x89, 0xe8]); // mov r8, r13
+                if self.config.tape_mode == TapeMode::Growable {
+                    self.asm.emit(&[0x4d, 0x29, 0xf0]); // sub r8, r14
+                }
+                self.call(CALL, Routine::Flush);
+                self.write_error(Message::StepLimit);
+                self.asm.emit(&[0x4c, 0x89, 0xf8]); // mov rax, r15
+                self.call(CALL, Routine::PrintUnsigned);
+                self.write_error(Message::StepLimitPosition);
+                self.asm.emit(&[0x4c, 0x89, 0xc0]); // mov rax, r8
+                self.call(CALL, Routine::PrintUnsigned);
+                self.write_error(Message::StepLimitEnd);
+                self.exit(1);
+            }
+        }
+    }
+
+    /// Flushes the output, writes `message` to stderr, and exits with a code of 1.
+    fn fail(&mut self, message: Message) {
+        self.call(CALL, Routine::Flush);
+        self.write_error(message);
+        self.exit(1);
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64", target_os = "linux"))]
+mod tests {
+    use std::{
+        fs,
+        io::Write,
+        os::unix::fs::PermissionsExt,
+        path::PathBuf,
+        process::{Command, Stdio},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{bf_parse, ir::Program, test_programs, Config, TapeMode};
+
+    use super::{
+        super::tests::{check_generator, exited, Run},
+        generate,
+    };
+
+    /// Builds `program` into an executable in a temporary directory, and runs it with `input`.
+    fn run_compiled(name: &str, program: &str, config: &Config, input: &[u8]) -> Run {
+        let commands = bf_parse(program).expect("parsing returned an error");
+        let elf = generate(&Program::new(&commands), config).expect("generating should succeed");
+
+        // Tests run in parallel, so every executable needs its own directory.
+        static BUILT: AtomicUsize = AtomicUsize::new(0);
+        let dir: PathBuf = std::env::temp_dir().join(format!(
+            "braincluck-elf-{}-{}",
+            std::process::id(),
+            BUILT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).expect("creating a directory should work");
+        let path = dir.join(name);
+        fs::write(&path, elf).expect("writing the executable should work");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .expect("making the executable runnable should work");
+
+        let mut child = Command::new(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("running the executable should work");
+        child
+            .stdin
+            .take()
+            .expect("stdin should be piped")
+            .write_all(input)
+            .expect("writing input should work");
+        let output = child
+            .wait_with_output()
+            .expect("running the executable should work");
+        fs::remove_dir_all(&dir).expect("removing a directory should work");
+
+        exited(
+            output.status.code(),
+            output.stdout,
+            &String::from_utf8_lossy(&output.stderr),
+        )
+    }
+
+    /// Compiled programs should behave the same as the interpreter.
+    #[test]
+    fn matches_interpreter() {
+        check_generator(run_compiled);
+    }
+
+    /// Checks that the compiled program stops the same way as running the optimized program, for 8 bit cells.
+    fn compare(program: &str, config: &Config) {
+        let mut cells = config.cells::<u8>();
+        let mut expected = vec![];
+        let result = Program::new(&bf_parse(program).expect("parsing returned an error")).run(
+            &mut cells,
+            &mut expected,
+            &mut std::io::empty(),
+        );
+        let (out, error) = run_compiled("compare", program, config, b"");
+
+        assert_eq!(out, expected, "{program} output should match");
+        assert_eq!(
+            error,
+            result.err().map(|err| err.inner().to_string()),
+            "{program} error should match"
+        );
+    }
+
+    /// The step limit should stop at the same op and cell as the interpreter, even after the tape grows left.
+    #[test]
+    fn step_limit() {
+        for step_limit in [0, 1, 5, 37, 100, 1000] {
+            let config = Config {
+                step_limit: Some(step_limit),
+                ..Config::default()
+            };
+            compare(test_programs::HELLO_WORLD, &config);
+            compare("+[>>>+[<]>]", &config);
+            compare("<<+>>>+[>+]", &config);
+            compare(">>>+<<<<<<+[<+]", &config);
+        }
+    }
+
+    #[test]
+    fn tape_edges() {
+        for tape_mode in [
+            TapeMode::Growable,
+            TapeMode::Bounded(4),
+            TapeMode::Circular(4),
+        ] {
+            let config = Config {
+                tape_mode,
+                ..Config::default()
+            };
+            for program in [
+                "+++[-<<+>>]<<.",
+                "+[<]+.",
+                ">>+>+[>]<.",
+                "+++[->>>>>++<<<<<]>>>>>.",
+                "+>>+>+<<<[>]>.",
+                "<<<<<<<<<<+.",
+            ] {
+                compare(program, &config);
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Compiles programs to a self-contained JavaScript function, for running in a browser or Node.
+
+use std::fmt::Write;
+
+use crate::{
+    ir::{Op, Program},
+    BraincluckError, CellWidth, Config, EofBehaviour, OutputMode, TapeMode,
+};
+
+use super::{require_wrapping, INITIAL_LENGTH};
+
+const FAIL: &str = r#"
+    function fail(message) {
+        const error = new Error(message);
+        error.output = Uint8Array.from(output);
+        throw error;
+    }
+"#;
+
+const MOVE_GROWABLE: &str = r#"
+    function moveBy(offset) {
+        p += offset;
+        if (p < 0) {
+            const grown = new tape.constructor(tape.length - p);
+            grown.set(tape, -p);
+            tape = grown;
+            p = 0;
+        } else if (p >= tape.length) {
+            const grown = new tape.constructor(Math.max(tape.length * 2, p + 1));
+            grown.set(tape);
+            tape = grown;
+        }
+    }
+"#;
+
+/// Reports the first cell past the end of the tape, as the [module documentation](super) describes.
+const MOVE_BOUNDED: &str = r#"
+    function moveBy(offset) {
+        const position = p + offset;
+        if (position < 0 || position >= tape.length) {
+            fail(`pointer moved to cell ${position < 0 ? -1 : tape.length}, outside of a tape with ${tape.length} cells`);
+        }
+        p = position;
+    }
+"#;
+
+const MOVE_CIRCULAR: &str = r#"
+    function moveBy(offset) {
+        p = (((p + offset) % tape.length) + tape.length) % tape.length;
+    }
+"#;
+
+const WRITE_BYTE: &str = r#"
+    function write() {
+        output.push(Number(tape[p] & MASK));
+    }
+"#;
+
+const WRITE_TEXT: &str = r#"
+    function write() {
+        const value = Number(tape[p]);
+        if (value < 0 || value > 255) {
+            fail("out of range integral type conversion attempted");
+        }
+        if (value < 0x80) {
+            output.push(value);
+        } else {
+            output.push(0xc0 | (value >> 6), 0x80 | (value & 0x3f));
+        }
+    }
+"#;
+
+const STEP: &str = r#"
+    let steps = 0;
+
+    function step() {
+        if (steps >= STEP_LIMIT) {
+            fail(`stopped after ${steps} steps at cell ${p}`);
+        }
+        steps++;
+    }
+"#;
+
+/// Compiles `program` into a JavaScript function declaration, `function run(input)`, which takes the input as a
+/// `Uint8Array` and returns the output as a `Uint8Array`.
+///
+/// The tape is a typed array of the cell width set in `config`, with `BigInt64Array` for 64 bit cells, so all
+/// arithmetic wraps around. Errors are thrown as an `Error` with the same message as the interpreter, and an
+/// `output` property with everything written before the error. Checked arithmetic is not supported.
+pub fn generate(program: &Program, config: &Config) -> Result<String, BraincluckError> {
+    require_wrapping(config)?;
+
+    let array = match config.cell_width {
+        CellWidth::I8 => "Int8Array",
+        CellWidth::U8 => "Uint8Array",
+        CellWidth::U16 => "Uint16Array",
+        CellWidth::U32 => "Uint32Array",
+        CellWidth::I64 => "BigInt64Array",
+    };
+    let big = config.cell_width == CellWidth::I64;
+    let (length, move_by) = match config.tape_mode {
+        TapeMode::Growable => (INITIAL_LENGTH, MOVE_GROWABLE),
+        TapeMode::Bounded(length) => (length, MOVE_BOUNDED),
+        TapeMode::Circular(length) => (length, MOVE_CIRCULAR),
+    };
+    // Numbers written into the tape, as BigInts for 64 bit cells.
+    let literal = |value: i64| {
+        if big {
+            format!("{value}n")
+        } else {
+            value.to_string()
+        }
+    };
+
+    let mut code = String::new();
+    writeln!(code, "/* Generated by braincluck. */")?;
+    writeln!(code, "function run(input) {{")?;
+    writeln!(code, "    let tape = new {array}({length});")?;
+    writeln!(code, "    let p = 0;")?;
+    writeln!(code, "    let consumed = 0;")?;
+    writeln!(code, "    const output = [];")?;
+    code.push_str(FAIL);
+
+    let uses = |f: fn(&Op) -> bool| program.ops().iter().any(f);
+    if uses(|op| matches!(op, Op::Move(_) | Op::Scan(_) | Op::MulAdd { .. })) {
+        code.push_str(move_by);
+    }
+    if uses(|op| matches!(op, Op::Output)) {
+        match config.output_mode {
+            OutputMode::Byte => code.push_str(&WRITE_BYTE.replace("MASK", &literal(0xff))),
+            OutputMode::Text => code.push_str(WRITE_TEXT),
+        }
+    }
+    if uses(|op| matches!(op, Op::Input)) {
+        write_input(&mut code, config.eof_behaviour, big)?;
+    }
+    if let Some(step_limit) = config.step_limit {
+        code.push_str(&STEP.replace("STEP_LIMIT", &step_limit.to_string()));
+    }
+    writeln!(code)?;
+
+    let step = if config.step_limit.is_some() {
+        "step(); "
+    } else {
+        ""
+    };
+    let mut depth = 1;
+    for op in program.ops() {
+        if let Op::LoopEnd(_) = op {
+            depth -= 1;
+        }
+        let indent = "    ".repeat(depth);
+
+        match *op {
+            Op::Add(delta) => {
+                // Only the bits that fit in a cell matter, which also keeps the delta exact as a JS number.
+                let delta = match config.cell_width {
+                    CellWidth::I8 | CellWidth::U8 => delta as i8 as i64,
+                    CellWidth::U16 => delta as i16 as i64,
+                    CellWidth::U32 => delta as i32 as i64,
+                    CellWidth::I64 => delta,
+                };
+                if delta < 0 {
+                    let magnitude = if big {
+                        format!("{}n", delta.unsigned_abs())
+                    } else {
+                        delta.unsigned_abs().to_string()
+                    };
+                    writeln!(code, "{indent}{step}tape[p] -= {magnitude};")?
+                } else {
+                    writeln!(code, "{indent}{step}tape[p] += {};", literal(delta))?
+                }
+            }
+            Op::Move(offset) => writeln!(code, "{indent}{step}moveBy({offset});")?,
+            Op::Output => writeln!(code, "{indent}{step}write();")?,
+            Op::Input => writeln!(code, "{indent}{step}read();")?,
+            // The end of a loop counts a step every time it checks the cell, but the start only counts one.
+            Op::LoopStart(_) => {
+                writeln!(code, "{indent}{step}while (tape[p]) {{")?;
+                depth += 1;
+            }
+            Op::LoopEnd(_) => {
+                if !step.is_empty() {
+                    writeln!(code, "{indent}    step();")?;
+                }
+                writeln!(code, "{indent}}}")?;
+            }
+            Op::Clear(_) => writeln!(code, "{indent}{step}tape[p] = {};", literal(0))?,
+            Op::Scan(offset) => writeln!(
+                code,
+                "{indent}{step}while (tape[p]) {{ {step}moveBy({offset}); }}"
+            )?,
+            Op::MulAdd { offset, factor } => {
+                // Math.imul keeps the low 32 bits of the product exact, which is all that fits in a cell.
+                let product = if big {
+                    format!("value * {factor}n")
+                } else {
+                    format!("Math.imul(value, {})", factor as i32)
+                };
+                writeln!(
+                    code,
+                    "{indent}{step}if (tape[p]) {{ const value = tape[p]; moveBy({offset}); tape[p] += {product}; moveBy({}); }}",
+                    -offset
+                )?
+            }
+        }
+    }
+
+    writeln!(code)?;
+    writeln!(code, "    return Uint8Array.from(output);")?;
+    writeln!(code, "}}")?;
+
+    Ok(code)
+}
+
+/// Writes the function that runs `,`.
+fn write_input(
+    code: &mut String,
+    eof_behaviour: EofBehaviour,
+    big: bool,
+) -> Result<(), BraincluckError> {
+    let byte = if big {
+        "BigInt(input[consumed++])"
+    } else {
+        "input[consumed++]"
+    };
+
+    writeln!(code)?;
+    writeln!(code, "    function read() {{")?;
+    writeln!(code, "        if (consumed < input.length) {{")?;
+    writeln!(code, "            tape[p] = {byte};")?;
+    match eof_behaviour {
+        EofBehaviour::Zero => {
+            writeln!(code, "        }} else {{")?;
+            writeln!(
+                code,
+                "            tape[p] = {};",
+                if big { "0n" } else { "0" }
+            )?;
+        }
+        EofBehaviour::NegativeOne => {
+            writeln!(code, "        }} else {{")?;
+            writeln!(
+                code,
+                "            tape[p] = {};",
+                if big { "-1n" } else { "-1" }
+            )?;
+        }
+        EofBehaviour::Unchanged => {}
+        EofBehaviour::Error => {
+            writeln!(code, "        }} else {{")?;
+            writeln!(
+                code,
+                "            fail(\"tried to read input past the end of the input\");"
+            )?;
+        }
+    }
+    writeln!(code, "        }}")?;
+    writeln!(code, "    }}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use boa_engine::{Context, Source};
+
+    use crate::{bf_parse, ir::Program, Config};
+
+    use super::{
+        super::tests::{check_generator, Run},
+        generate,
+    };
+
+    /// Compiles `program` to JavaScript and runs it with `input` in an embedded engine, returning the output and
+    /// the message of any error thrown.
+    fn run_compiled(program: &str, config: &Config, input: &[u8]) -> Run {
+        let commands = bf_parse(program).expect("parsing returned an error");
+        let code = generate(&Program::new(&commands), config).expect("generating should succeed");
+
+        let input = input
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let script = format!(
+            "{code}
+            (() => {{
+                let output;
+                let error = '';
+                try {{
+                    output = run(new Uint8Array([{input}]));
+                }} catch (e) {{
+                    output = e.output;
+                    error = e.message;
+                }}
+                return Array.from(output).join(',') + '\\n' + error;
+            }})()"
+        );
+        let result = Context::default()
+            .eval(Source::from_bytes(&script))
+            .expect("the script should run");
+        let result = result
+            .as_string()
+            .expect("the script should return a string")
+            .to_std_string_escaped();
+
+        let (output, error) = result
+            .split_once('\n')
+            .expect("the result should have a newline");
+        let output = output
+            .split(',')
+            .filter(|byte| !byte.is_empty())
+            .map(|byte| byte.parse().expect("output should be bytes"))
+            .collect();
+        (
+            output,
+            Some(error.to_string()).filter(|error| !error.is_empty()),
+        )
+    }
+
+    /// Compiled programs should behave the same as the interpreter.
+    #[test]
+    fn matches_interpreter() {
+        check_generator(|_, program, config, input| run_compiled(program, config, input));
+    }
+}
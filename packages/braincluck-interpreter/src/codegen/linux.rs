@@ -0,0 +1,243 @@
+//! The parts shared by the generators that build standalone x86-64 Linux executables, [`elf`](super::elf) and
+//! [`asm`](super::asm): the memory layout, the routines, and the error messages.
+//!
+//! While running, rbx holds the output buffer, rbp the number of bytes in it, r12 the tape, r13 the index, r15
+//! the number of steps, and r9 the step limit. r14 holds the length of a fixed size tape, or the lowest index
+//! reached on a growable tape, which the interpreter would see as cell 0.
+
+use std::io;
+
+use crate::{BraincluckError, CellWidth, Config, TapeMode};
+
+/// The size of the output buffer, in bytes.
+pub(super) const BUFFER_SIZE: u32 = 4096;
+
+/// How much memory is reserved for a growable tape, in bytes. The pointer starts in the middle, so it can grow
+/// by half of this in either direction. Pages are only allocated once they're used.
+pub(super) const GROWABLE_SIZE: u64 = 1 << 30;
+
+/// Returns the size of a cell in bytes.
+pub(super) fn width(config: &Config) -> u32 {
+    match config.cell_width {
+        CellWidth::I8 | CellWidth::U8 => 1,
+        CellWidth::U16 => 2,
+        CellWidth::U32 => 4,
+        CellWidth::I64 => 8,
+    }
+}
+
+/// Returns how many bytes to map for the tape, the index to start at, and what r14 starts as.
+pub(super) fn tape(config: &Config) -> (u64, u64, u64) {
+    let width = width(config) as u64;
+    match config.tape_mode {
+        TapeMode::Growable => {
+            let cells = GROWABLE_SIZE / width;
+            (GROWABLE_SIZE, cells / 2, cells / 2)
+        }
+        TapeMode::Bounded(length) | TapeMode::Circular(length) => {
+            (length as u64 * width, 0, length as u64)
+        }
+    }
+}
+
+/// The shared code that the compiled ops call or jump to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Routine {
+    Flush,
+    Putc,
+    Getc,
+    WriteError,
+    PrintUnsigned,
+    PrintSigned,
+    UnexpectedEof,
+    Conversion,
+    OutOfMemory,
+    OutOfBounds,
+    StepLimitExceeded,
+}
+
+impl Routine {
+    pub(super) const ALL: [Routine; 11] = [
+        Routine::Flush,
+        Routine::Putc,
+        Routine::Getc,
+        Routine::WriteError,
+        Routine::PrintUnsigned,
+        Routine::PrintSigned,
+        Routine::UnexpectedEof,
+        Routine::Conversion,
+        Routine::OutOfMemory,
+        Routine::OutOfBounds,
+        Routine::StepLimitExceeded,
+    ];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Routine::Flush => "flush",
+            Routine::Putc => "putc",
+            Routine::Getc => "getc",
+            Routine::WriteError => "write_error",
+            Routine::PrintUnsigned => "print_unsigned",
+            Routine::PrintSigned => "print_signed",
+            Routine::UnexpectedEof => "unexpected_eof",
+            Routine::Conversion => "conversion",
+            Routine::OutOfMemory => "out_of_memory",
+            Routine::OutOfBounds => "out_of_bounds",
+            Routine::StepLimitExceeded => "step_limit_exceeded",
+        }
+    }
+
+    /// Returns what the routine does, for the routines that do more than fail with a message.
+    pub(super) fn description(self) -> Option<&'static str> {
+        match self {
+            Routine::Flush => Some("Writes out the output buffer."),
+            Routine::Putc => Some("Adds the byte in al to the output buffer."),
+            Routine::Getc => Some(
+                "Flushes the output buffer, so any prompts are shown before waiting for input, and reads a byte into \
+                 rax, or -1 at the end of the input.",
+            ),
+            Routine::WriteError => Some("Writes rdx bytes from rsi to stderr."),
+            Routine::PrintUnsigned => Some("Writes rax to stderr as an unsigned number."),
+            Routine::PrintSigned => Some("Writes rax to stderr as a signed number."),
+            Routine::OutOfBounds => {
+                Some("Stops with the position in rax being outside of the tape.")
+            }
+            Routine::StepLimitExceeded => Some("The step that went over the limit isn't counted."),
+            Routine::UnexpectedEof | Routine::Conversion | Routine::OutOfMemory => None,
+        }
+    }
+}
+
+/// Numbers that stand in for the ones filled in while running, so messages can be split around them.
+const FIRST: u32 = 1_234_567;
+const SECOND: u32 = 7_654_321;
+
+/// The text that the executables write to stderr, made from the interpreter's errors so they always match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Message {
+    UnexpectedEof,
+    Conversion,
+    OutOfMemory,
+    /// The out of bounds error up to the position, between the position and the length, and after the length.
+    OutOfBounds,
+    OutOfBoundsLength,
+    OutOfBoundsCells,
+    /// The step limit error up to the steps, between the steps and the position, and after the position.
+    StepLimit,
+    StepLimitPosition,
+    StepLimitEnd,
+    /// Written before a negative number.
+    Minus,
+}
+
+impl Message {
+    pub(super) const ALL: [Message; 10] = [
+        Message::UnexpectedEof,
+        Message::Conversion,
+        Message::OutOfMemory,
+        Message::OutOfBounds,
+        Message::OutOfBoundsLength,
+        Message::OutOfBoundsCells,
+        Message::StepLimit,
+        Message::StepLimitPosition,
+        Message::StepLimitEnd,
+        Message::Minus,
+    ];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Message::UnexpectedEof => "unexpected_eof_message",
+            Message::Conversion => "conversion_message",
+            Message::OutOfMemory => "out_of_memory_message",
+            Message::OutOfBounds => "out_of_bounds_message",
+            Message::OutOfBoundsLength => "out_of_bounds_length_message",
+            Message::OutOfBoundsCells => "out_of_bounds_cells_message",
+            Message::StepLimit => "step_limit_message",
+            Message::StepLimitPosition => "step_limit_position_message",
+            Message::StepLimitEnd => "step_limit_end_message",
+            Message::Minus => "minus_message",
+        }
+    }
+
+    pub(super) fn text(self) -> String {
+        let out_of_bounds = || BraincluckError::PointerOutOfBounds {
+            position: FIRST as isize,
+            length: SECOND as usize,
+        };
+        let step_limit = || BraincluckError::StepLimitExceeded {
+            steps: FIRST.into(),
+            position: SECOND as usize,
+        };
+
+        match self {
+            Message::UnexpectedEof => line(BraincluckError::UnexpectedEof),
+            Message::Conversion => line(u8::try_from(-1_i64).unwrap_err().into()),
+            Message::OutOfMemory => line(io::Error::from(io::ErrorKind::OutOfMemory).into()),
+            Message::OutOfBounds => part(out_of_bounds(), 0),
+            Message::OutOfBoundsLength => part(out_of_bounds(), 1),
+            Message::OutOfBoundsCells => part(out_of_bounds(), 2),
+            Message::StepLimit => part(step_limit(), 0),
+            Message::StepLimitPosition => part(step_limit(), 1),
+            Message::StepLimitEnd => part(step_limit(), 2),
+            Message::Minus => "-".to_string(),
+        }
+    }
+}
+
+/// Returns `err` as the line that the executables write to stderr.
+fn line(err: BraincluckError) -> String {
+    format!("error: {err}\n")
+}
+
+/// Returns the part of `err` before [`FIRST`], between it and [`SECOND`], or after that.
+fn part(err: BraincluckError, index: usize) -> String {
+    let line = line(err);
+    let (before, rest) = line
+        .split_once(&FIRST.to_string())
+        .expect("the message should have the first number");
+    let (between, after) = rest
+        .split_once(&SECOND.to_string())
+        .expect("the message should have the second number");
+    [before, between, after][index].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Message;
+
+    #[test]
+    fn messages() {
+        let text = |messages: &[Message]| {
+            messages
+                .iter()
+                .map(|message| message.text())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            text(&[
+                Message::OutOfBounds,
+                Message::OutOfBoundsLength,
+                Message::OutOfBoundsCells
+            ]),
+            [
+                "error: pointer moved to cell ",
+                ", outside of a tape with ",
+                " cells\n"
+            ]
+        );
+        assert_eq!(
+            text(&[
+                Message::StepLimit,
+                Message::StepLimitPosition,
+                Message::StepLimitEnd
+            ]),
+            ["error: stopped after ", " steps at cell ", "\n"]
+        );
+        assert_eq!(
+            Message::Conversion.text(),
+            "error: out of range integral type conversion attempted\n"
+        );
+        assert_eq!(Message::OutOfMemory.text(), "error: out of memory\n");
+    }
+}
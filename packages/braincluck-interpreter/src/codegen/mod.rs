@@ -0,0 +1,126 @@
+//! Code generators that compile an optimized [`Program`](crate::ir::Program) into a standalone program in another
+//! language, which can then be built with that language's tools.
+//!
+//! The generated programs follow the same [`Config`] as an [`Interpreter`](crate::Interpreter), and stop with
+//! the same error messages, written to stderr, and a non-zero exit code. Settings that a generator can't follow
+//! return a [`BraincluckError::UnsupportedConfig`].
+//!
+//! A few details of the interpreter's behaviour aren't obvious from the code, and every generator follows them:
+//!
+//! - Moving past either end of a bounded tape reports the first cell past that end, not where the pointer would have
+//!   ended up, the same as moving one cell at a time would.
+//! - With [`OutputMode::Text`](crate::OutputMode::Text), cells are written as the character with that code point, so
+//!   only 0 to 255 can be written.
+
+use crate::{ArithmeticMode, BraincluckError, Config};
+
+pub mod asm;
+pub mod c;
+pub mod elf;
+pub mod js;
+mod linux;
+pub mod wasm;
+
+/// The number of cells a growable tape starts with, the same as [`Cells::default`](crate::Cells).
+const INITIAL_LENGTH: usize = 30000;
+
+/// Returns an error if the program would need checked arithmetic, which the generators don't support.
+fn require_wrapping(config: &Config) -> Result<(), BraincluckError> {
+    match config.arithmetic_mode {
+        ArithmeticMode::Wrapping => Ok(()),
+        ArithmeticMode::Checked => Err(BraincluckError::UnsupportedConfig("checked arithmetic")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{ir::Program, test_programs, ArithmeticMode, BraincluckError, Config};
+
+    use super::{asm, c, elf, js, require_wrapping, wasm};
+
+    /// What a compiled program wrote, and the message of the error it stopped with, if any.
+    pub(super) type Run = (Vec<u8>, Option<String>);
+
+    /// Turns how a compiled executable exited into a [`Run`]. Executables stop with an exit code of 1 after
+    /// writing `error: ` and the message to stderr.
+    pub(super) fn exited(code: Option<i32>, stdout: Vec<u8>, stderr: &str) -> Run {
+        match code {
+            Some(0) => (stdout, None),
+            Some(1) => {
+                let message = stderr
+                    .strip_prefix("error: ")
+                    .and_then(|message| message.strip_suffix('\n'))
+                    .unwrap_or_else(|| panic!("unexpected error output {stderr:?}"));
+                (stdout, Some(message.to_string()))
+            }
+            code => panic!("unexpected exit code {code:?}: {stderr}"),
+        }
+    }
+
+    /// Skips a test that can't run because `missing` tools weren't found, except when the `CI` environment
+    /// variable is set, where the test fails instead of passing without checking anything.
+    pub(super) fn missing_tools(missing: &str) {
+        assert!(env::var_os("CI").is_none(), "{missing}, but CI is set");
+        eprintln!("skipping, {missing}");
+    }
+
+    /// Checks that the shared test programs behave the same when compiled by a generator as they do in the
+    /// interpreter. `run` compiles the program with the given name and settings, and runs it with the given input.
+    pub(super) fn check_generator(run: impl Fn(&str, &str, &Config, &[u8]) -> Run) {
+        for (name, program, interpreter, input) in test_programs::programs() {
+            let expected = interpreter
+                .run_to_vec(program, input)
+                .expect("interpret should succeed");
+            let (out, error) = run(name, program, interpreter.config(), input);
+
+            assert_eq!(error, None, "{name} should succeed");
+            assert_eq!(out, expected, "{name} output should match");
+        }
+
+        for (name, program, interpreter) in test_programs::errors() {
+            if require_wrapping(interpreter.config()).is_err() {
+                continue;
+            }
+
+            let mut expected = vec![];
+            let err = interpreter
+                .run(program, &mut expected, &mut std::io::empty())
+                .expect_err("interpret should fail");
+            let (out, error) = run(name, program, interpreter.config(), b"");
+
+            assert_eq!(out, expected, "{name} output should match");
+            assert_eq!(
+                error,
+                Some(err.inner().to_string()),
+                "{name} error should match"
+            );
+        }
+    }
+
+    #[test]
+    fn checked_unsupported() {
+        let config = Config {
+            arithmetic_mode: ArithmeticMode::Checked,
+            ..Config::default()
+        };
+        let program = Program::default();
+
+        for (generator, result) in [
+            ("c", c::generate(&program, &config).map(drop)),
+            ("wasm", wasm::generate(&program, &config).map(drop)),
+            ("elf", elf::generate(&program, &config).map(drop)),
+            (
+                "asm",
+                asm::generate(&program, &config, "", asm::Syntax::Gas).map(drop),
+            ),
+            ("js", js::generate(&program, &config).map(drop)),
+        ] {
+            assert!(
+                matches!(result, Err(BraincluckError::UnsupportedConfig(_))),
+                "{generator} should not support checked arithmetic"
+            );
+        }
+    }
+}
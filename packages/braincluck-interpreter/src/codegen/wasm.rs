@@ -0,0 +1,844 @@
+//! Compiles programs to WebAssembly modules.
+//!
+//! The generated module uses its linear memory as the tape, and exports it as `memory`, along with a `run`
+//! function that runs the program. The host provides three functions in the `env` module:
+//!
+//! - `read() -> i32` returns the next byte of input, or a negative number if there is no input left.
+//! - `write(i32)` writes the lowest byte of its argument to the output.
+//! - `error(i32, i64, i64)` is called with a kind of error and its details just before the program traps.
+//!   [`error`] turns these back into a [`BraincluckError`].
+
+use std::io;
+
+use crate::{
+    ir::{Op, Program},
+    BraincluckError, CellWidth, Config, EofBehaviour, OutputMode, TapeMode,
+};
+
+use super::{require_wrapping, INITIAL_LENGTH};
+
+const PAGE_SIZE: usize = 65536;
+/// The most bytes a 32 bit linear memory can hold.
+const MAX_MEMORY: u64 = 1 << 32;
+
+/// The kinds of error passed to the `error` import.
+pub const ERROR_UNEXPECTED_EOF: i32 = 0;
+pub const ERROR_POINTER_OUT_OF_BOUNDS: i32 = 1;
+pub const ERROR_STEP_LIMIT_EXCEEDED: i32 = 2;
+pub const ERROR_CONVERSION: i32 = 3;
+pub const ERROR_OUT_OF_MEMORY: i32 = 4;
+
+/// Turns the arguments of a call to the `error` import back into the error it stands for.
+///
+/// For [`ERROR_POINTER_OUT_OF_BOUNDS`], the details are the position and length of the tape. For
+/// [`ERROR_STEP_LIMIT_EXCEEDED`], they are the number of steps and the position of the pointer. Kinds that no
+/// generated module uses become an [`io::ErrorKind::InvalidData`] error.
+pub fn error(kind: i32, first: i64, second: i64) -> BraincluckError {
+    match kind {
+        ERROR_UNEXPECTED_EOF => BraincluckError::UnexpectedEof,
+        ERROR_POINTER_OUT_OF_BOUNDS => BraincluckError::PointerOutOfBounds {
+            position: first as isize,
+            length: second as usize,
+        },
+        ERROR_STEP_LIMIT_EXCEEDED => BraincluckError::StepLimitExceeded {
+            steps: first as u64,
+            position: second as usize,
+        },
+        // A `TryFromIntError` can only be made by a conversion that fails.
+        ERROR_CONVERSION => u8::try_from(-1_i64)
+            .expect_err("-1 should not fit in a u8")
+            .into(),
+        ERROR_OUT_OF_MEMORY => {
+            io::Error::new(io::ErrorKind::OutOfMemory, "the tape couldn't grow").into()
+        }
+        kind => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown kind of error {kind}"),
+        )
+        .into(),
+    }
+}
+
+// Function indices. The imports come first.
+const READ: u32 = 0;
+const WRITE: u32 = 1;
+const ERROR: u32 = 2;
+const RUN: u32 = 3;
+const MOVE_BY: u32 = 4;
+const STEP: u32 = 5;
+const OUTPUT: u32 = 6;
+const INPUT: u32 = 7;
+const ENSURE: u32 = 8;
+
+// Global indices.
+const POINTER: u32 = 0;
+const LENGTH: u32 = 1;
+const STEPS: u32 = 2;
+
+// Type indices.
+const TYPE_READ: u32 = 0;
+const TYPE_I32: u32 = 1;
+const TYPE_ERROR: u32 = 2;
+const TYPE_EMPTY: u32 = 3;
+
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+const EMPTY_BLOCK: u8 = 0x40;
+
+/// Compiles `program` into a WebAssembly module. See the [module documentation](self) for what it imports and
+/// exports.
+///
+/// Checked arithmetic is not supported, and neither are fixed size tapes that don't fit in a 32 bit memory.
+pub fn generate(program: &Program, config: &Config) -> Result<Vec<u8>, BraincluckError> {
+    require_wrapping(config)?;
+
+    let cells = Cells::new(config.cell_width);
+    let length = match config.tape_mode {
+        TapeMode::Growable => INITIAL_LENGTH,
+        TapeMode::Bounded(length) | TapeMode::Circular(length) => length,
+    };
+    // The pointer is a signed i32, so the length has to fit in one as well.
+    let bytes = (length as u64).saturating_mul(1 << cells.shift);
+    if i32::try_from(length).is_err() || bytes > MAX_MEMORY {
+        return Err(BraincluckError::UnsupportedConfig(
+            "a tape that doesn't fit in 32 bit memory",
+        ));
+    }
+    let pages = bytes.div_ceil(PAGE_SIZE as u64);
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+
+    let types = vec![
+        func_type(&[], &[I32]),
+        func_type(&[I32], &[]),
+        func_type(&[I32, I64, I64], &[]),
+        func_type(&[], &[]),
+    ];
+    section(&mut module, 1, &types);
+
+    let imports = vec![
+        import("read", TYPE_READ),
+        import("write", TYPE_I32),
+        import("error", TYPE_ERROR),
+    ];
+    section(&mut module, 2, &imports);
+
+    // run, move_by, step, output, input, ensure
+    let functions: Vec<Vec<u8>> = [
+        TYPE_EMPTY, TYPE_I32, TYPE_EMPTY, TYPE_EMPTY, TYPE_EMPTY, TYPE_I32,
+    ]
+    .iter()
+    .map(|&index| uleb(index as u64))
+    .collect();
+    section(&mut module, 3, &functions);
+
+    let mut memory = vec![0x00];
+    memory.extend(uleb(pages.max(1)));
+    section(&mut module, 5, &[memory]);
+
+    let globals = vec![
+        global(I32, Code::new().i32_const(0)),
+        global(I32, Code::new().i32_const(length as i32)),
+        global(I64, Code::new().i64_const(0)),
+    ];
+    section(&mut module, 6, &globals);
+
+    let exports = vec![export("run", 0x00, RUN), export("memory", 0x02, 0)];
+    section(&mut module, 7, &exports);
+
+    let bodies = vec![
+        function(&[I64], run(program, config, &cells)),
+        function(&[I32, I32], move_by(config.tape_mode, &cells)),
+        function(&[], step(config.step_limit)),
+        function(&[I64], output(config.output_mode, &cells)),
+        function(&[I32], input(config.eof_behaviour, &cells)),
+        function(&[I32], ensure(&cells)),
+    ];
+    section(&mut module, 10, &bodies);
+
+    Ok(module)
+}
+
+/// How cells of a given width are stored in memory. Cells are always worked on as `i64`s, and truncated when
+/// they are stored.
+struct Cells {
+    /// How far to shift a cell index to get its address.
+    shift: u32,
+    /// The opcode to load a cell, zero extended.
+    load: u8,
+    /// The opcode to load a cell, sign extended if the cell is signed.
+    load_signed: u8,
+    /// The opcode to store a cell.
+    store: u8,
+}
+
+impl Cells {
+    fn new(cell_width: CellWidth) -> Self {
+        let (shift, load, load_signed, store) = match cell_width {
+            CellWidth::I8 => (0, 0x31, 0x30, 0x3c),
+            CellWidth::U8 => (0, 0x31, 0x31, 0x3c),
+            CellWidth::U16 => (1, 0x33, 0x33, 0x3d),
+            CellWidth::U32 => (2, 0x35, 0x35, 0x3e),
+            CellWidth::I64 => (3, 0x29, 0x29, 0x37),
+        };
+
+        Self {
+            shift,
+            load,
+            load_signed,
+            store,
+        }
+    }
+}
+
+/// The body of `run`, which runs the program itself. Local 0 holds the value being multiplied by
+/// [`Op::MulAdd`].
+fn run(program: &Program, config: &Config, cells: &Cells) -> Code {
+    let counted = config.step_limit.is_some();
+    let mut code = Code::new();
+    for op in program.ops() {
+        if counted && !matches!(op, Op::LoopEnd(_)) {
+            code = code.call(STEP);
+        }
+
+        code = match *op {
+            Op::Add(delta) => code
+                .address(cells)
+                .address(cells)
+                .load(cells.load)
+                .i64_const(delta)
+                .op(0x7c) // i64.add
+                .store(cells.store),
+            Op::Move(offset) => code.i32_const(offset as i32).call(MOVE_BY),
+            Op::Output => code.call(OUTPUT),
+            Op::Input => code.call(INPUT),
+            Op::LoopStart(_) => code
+                .block(0x02)
+                .block(0x03)
+                .address(cells)
+                .load(cells.load)
+                .op(0x50) // i64.eqz
+                .br_if(1),
+            Op::LoopEnd(_) => {
+                if counted {
+                    code = code.call(STEP);
+                }
+                code.br(0).end().end()
+            }
+            Op::Clear(_) => code.address(cells).i64_const(0).store(cells.store),
+            Op::Scan(offset) => {
+                code = code
+                    .block(0x02)
+                    .block(0x03)
+                    .address(cells)
+                    .load(cells.load)
+                    .op(0x50) // i64.eqz
+                    .br_if(1);
+                if counted {
+                    code = code.call(STEP);
+                }
+                code.i32_const(offset as i32)
+                    .call(MOVE_BY)
+                    .br(0)
+                    .end()
+                    .end()
+            }
+            Op::MulAdd { offset, factor } => code
+                .address(cells)
+                .load(cells.load)
+                .local_tee(0)
+                .op(0x50) // i64.eqz
+                .op(0x45) // i32.eqz
+                .block(0x04)
+                .i32_const(offset as i32)
+                .call(MOVE_BY)
+                .address(cells)
+                .address(cells)
+                .load(cells.load)
+                .local_get(0)
+                .i64_const(factor)
+                .op(0x7e) // i64.mul
+                .op(0x7c) // i64.add
+                .store(cells.store)
+                .i32_const(-offset as i32)
+                .call(MOVE_BY)
+                .end(),
+        };
+    }
+
+    code
+}
+
+/// The body of `move_by(offset)`, which moves the pointer following the [`TapeMode`]. Local 1 holds the new
+/// position, and local 2 how far a growable tape needs to grow to the left.
+fn move_by(tape_mode: TapeMode, cells: &Cells) -> Code {
+    let code = Code::new()
+        .global_get(POINTER)
+        .local_get(0)
+        .op(0x6a) // i32.add
+        .local_set(1);
+
+    match tape_mode {
+        TapeMode::Growable => code
+            .local_get(1)
+            .i32_const(0)
+            .op(0x48) // i32.lt_s
+            .block(0x04)
+            // Make room on the left by moving every cell to the right.
+            .i32_const(0)
+            .local_get(1)
+            .op(0x6b) // i32.sub
+            .local_set(2)
+            .global_get(LENGTH)
+            .local_get(2)
+            .op(0x6a) // i32.add
+            .call(ENSURE)
+            .local_get(2)
+            .shift(cells)
+            .i32_const(0)
+            .global_get(LENGTH)
+            .shift(cells)
+            .memory_copy()
+            .i32_const(0)
+            .i32_const(0)
+            .local_get(2)
+            .shift(cells)
+            .memory_fill()
+            .global_get(LENGTH)
+            .local_get(2)
+            .op(0x6a) // i32.add
+            .global_set(LENGTH)
+            .i32_const(0)
+            .global_set(POINTER)
+            .op(0x05) // else
+            .local_get(1)
+            .global_set(POINTER)
+            .local_get(1)
+            .global_get(LENGTH)
+            .op(0x4e) // i32.ge_s
+            .block(0x04)
+            // Memory past the end of the tape has never been written to, so it's still zeroed.
+            .local_get(1)
+            .i32_const(1)
+            .op(0x6a) // i32.add
+            .local_tee(1)
+            .call(ENSURE)
+            .local_get(1)
+            .global_set(LENGTH)
+            .end()
+            .end(),
+        TapeMode::Bounded(length) => code
+            .local_get(1)
+            .i32_const(length as i32)
+            .op(0x4f) // i32.ge_u, which also catches negative positions
+            .block(0x04)
+            .i32_const(ERROR_POINTER_OUT_OF_BOUNDS)
+            // The first cell past the end, see the [module documentation](super).
+            .i64_const(-1)
+            .i64_const(length as i64)
+            .local_get(1)
+            .i32_const(0)
+            .op(0x48) // i32.lt_s
+            .op(0x1b) // select
+            .i64_const(length as i64)
+            .call(ERROR)
+            .op(0x00) // unreachable
+            .end()
+            .local_get(1)
+            .global_set(POINTER),
+        TapeMode::Circular(length) => code
+            .local_get(1)
+            .i32_const(length as i32)
+            .op(0x6f) // i32.rem_s
+            .local_tee(1)
+            .i32_const(0)
+            .op(0x48) // i32.lt_s
+            .block(0x04)
+            .local_get(1)
+            .i32_const(length as i32)
+            .op(0x6a) // i32.add
+            .local_set(1)
+            .end()
+            .local_get(1)
+            .global_set(POINTER),
+    }
+}
+
+/// The body of `step`, which counts a step towards the step limit.
+fn step(step_limit: Option<u64>) -> Code {
+    match step_limit {
+        None => Code::new(),
+        Some(step_limit) => Code::new()
+            .global_get(STEPS)
+            .i64_const(step_limit as i64)
+            .op(0x5a) // i64.ge_u
+            .block(0x04)
+            .i32_const(ERROR_STEP_LIMIT_EXCEEDED)
+            .global_get(STEPS)
+            .global_get(POINTER)
+            .op(0xad) // i64.extend_i32_u
+            .call(ERROR)
+            .op(0x00) // unreachable
+            .end()
+            .global_get(STEPS)
+            .i64_const(1)
+            .op(0x7c) // i64.add
+            .global_set(STEPS),
+    }
+}
+
+/// The body of `output`, which writes the current cell following the [`OutputMode`]. Local 0 holds the value
+/// of the cell.
+fn output(output_mode: OutputMode, cells: &Cells) -> Code {
+    match output_mode {
+        OutputMode::Byte => Code::new()
+            .address(cells)
+            .load(cells.load)
+            .op(0xa7) // i32.wrap_i64
+            .call(WRITE),
+        // Only 0 to 255 can be written, see the [module documentation](super).
+        OutputMode::Text => Code::new()
+            .address(cells)
+            .load(cells.load_signed)
+            .local_tee(0)
+            .i64_const(255)
+            .op(0x56) // i64.gt_u, which also catches negative values
+            .block(0x04)
+            .i32_const(ERROR_CONVERSION)
+            .i64_const(0)
+            .i64_const(0)
+            .call(ERROR)
+            .op(0x00) // unreachable
+            .end()
+            .local_get(0)
+            .i64_const(0x80)
+            .op(0x54) // i64.lt_u
+            .block(0x04)
+            .local_get(0)
+            .op(0xa7) // i32.wrap_i64
+            .call(WRITE)
+            .op(0x05) // else
+            .local_get(0)
+            .i64_const(6)
+            .op(0x88) // i64.shr_u
+            .i64_const(0xc0)
+            .op(0x84) // i64.or
+            .op(0xa7) // i32.wrap_i64
+            .call(WRITE)
+            .local_get(0)
+            .i64_const(0x3f)
+            .op(0x83) // i64.and
+            .i64_const(0x80)
+            .op(0x84) // i64.or
+            .op(0xa7) // i32.wrap_i64
+            .call(WRITE)
+            .end(),
+    }
+}
+
+/// The body of `input`, which reads a byte into the current cell following the [`EofBehaviour`]. Local 0
+/// holds the byte that was read.
+fn input(eof_behaviour: EofBehaviour, cells: &Cells) -> Code {
+    let code = Code::new()
+        .call(READ)
+        .local_tee(0)
+        .i32_const(0)
+        .op(0x48) // i32.lt_s
+        .block(0x04);
+
+    let code = match eof_behaviour {
+        EofBehaviour::Zero => code.address(cells).i64_const(0).store(cells.store),
+        EofBehaviour::NegativeOne => code.address(cells).i64_const(-1).store(cells.store),
+        EofBehaviour::Unchanged => code,
+        EofBehaviour::Error => code
+            .i32_const(ERROR_UNEXPECTED_EOF)
+            .i64_const(0)
+            .i64_const(0)
+            .call(ERROR)
+            .op(0x00), // unreachable
+    };
+
+    code.op(0x05) // else
+        .address(cells)
+        .local_get(0)
+        .op(0xad) // i64.extend_i32_u
+        .store(cells.store)
+        .end()
+}
+
+/// The body of `ensure(cells)`, which grows the memory to fit at least that many cells. Local 1 holds how
+/// many pages are missing.
+fn ensure(cells: &Cells) -> Code {
+    Code::new()
+        .local_get(0)
+        .shift(cells)
+        .i32_const(PAGE_SIZE as i32 - 1)
+        .op(0x6a) // i32.add
+        .i32_const(16)
+        .op(0x76) // i32.shr_u
+        .memory_size()
+        .op(0x6b) // i32.sub
+        .local_tee(1)
+        .i32_const(0)
+        .op(0x4a) // i32.gt_s
+        .block(0x04)
+        .local_get(1)
+        .memory_grow()
+        .i32_const(-1)
+        .op(0x46) // i32.eq
+        .block(0x04)
+        .i32_const(ERROR_OUT_OF_MEMORY)
+        .i64_const(0)
+        .i64_const(0)
+        .call(ERROR)
+        .op(0x00) // unreachable
+        .end()
+        .end()
+}
+
+/// A builder for the instructions of a function body.
+struct Code(Vec<u8>);
+
+impl Code {
+    fn new() -> Self {
+        Self(vec![])
+    }
+
+    fn op(mut self, opcode: u8) -> Self {
+        self.0.push(opcode);
+        self
+    }
+
+    fn with_uleb(mut self, opcode: u8, value: u32) -> Self {
+        self.0.push(opcode);
+        self.0.extend(uleb(value as u64));
+        self
+    }
+
+    fn i32_const(mut self, value: i32) -> Self {
+        self.0.push(0x41);
+        self.0.extend(sleb(value as i64));
+        self
+    }
+
+    fn i64_const(mut self, value: i64) -> Self {
+        self.0.push(0x42);
+        self.0.extend(sleb(value));
+        self
+    }
+
+    fn local_get(self, index: u32) -> Self {
+        self.with_uleb(0x20, index)
+    }
+
+    fn local_set(self, index: u32) -> Self {
+        self.with_uleb(0x21, index)
+    }
+
+    fn local_tee(self, index: u32) -> Self {
+        self.with_uleb(0x22, index)
+    }
+
+    fn global_get(self, index: u32) -> Self {
+        self.with_uleb(0x23, index)
+    }
+
+    fn global_set(self, index: u32) -> Self {
+        self.with_uleb(0x24, index)
+    }
+
+    fn call(self, index: u32) -> Self {
+        self.with_uleb(0x10, index)
+    }
+
+    fn br(self, depth: u32) -> Self {
+        self.with_uleb(0x0c, depth)
+    }
+
+    fn br_if(self, depth: u32) -> Self {
+        self.with_uleb(0x0d, depth)
+    }
+
+    /// Starts a `block` (`0x02`), `loop` (`0x03`), or `if` (`0x04`) with no parameters or results.
+    fn block(mut self, opcode: u8) -> Self {
+        self.0.extend([opcode, EMPTY_BLOCK]);
+        self
+    }
+
+    fn end(self) -> Self {
+        self.op(0x0b)
+    }
+
+    /// Multiplies the `i32` on the stack by the size of a cell.
+    fn shift(self, cells: &Cells) -> Self {
+        if cells.shift == 0 {
+            self
+        } else {
+            self.i32_const(cells.shift as i32).op(0x74) // i32.shl
+        }
+    }
+
+    /// Pushes the address of the current cell.
+    fn address(self, cells: &Cells) -> Self {
+        self.global_get(POINTER).shift(cells)
+    }
+
+    fn load(mut self, opcode: u8) -> Self {
+        self.0.extend([opcode, 0, 0]);
+        self
+    }
+
+    fn store(mut self, opcode: u8) -> Self {
+        self.0.extend([opcode, 0, 0]);
+        self
+    }
+
+    fn memory_size(mut self) -> Self {
+        self.0.extend([0x3f, 0x00]);
+        self
+    }
+
+    fn memory_grow(mut self) -> Self {
+        self.0.extend([0x40, 0x00]);
+        self
+    }
+
+    fn memory_copy(mut self) -> Self {
+        self.0.extend([0xfc, 10, 0, 0]);
+        self
+    }
+
+    fn memory_fill(mut self) -> Self {
+        self.0.extend([0xfc, 11, 0]);
+        self
+    }
+}
+
+fn uleb(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn sleb(mut value: i64) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Encodes a vector of already encoded items, prefixed with its length.
+fn vector(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = uleb(items.len() as u64);
+    for item in items {
+        bytes.extend(item);
+    }
+    bytes
+}
+
+/// Encodes a string or byte vector, prefixed with its length.
+fn name(name: &str) -> Vec<u8> {
+    let mut bytes = uleb(name.len() as u64);
+    bytes.extend(name.as_bytes());
+    bytes
+}
+
+fn section(module: &mut Vec<u8>, id: u8, items: &[Vec<u8>]) {
+    let contents = vector(items);
+    module.push(id);
+    module.extend(uleb(contents.len() as u64));
+    module.extend(contents);
+}
+
+fn func_type(params: &[u8], results: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x60];
+    bytes.extend(uleb(params.len() as u64));
+    bytes.extend(params);
+    bytes.extend(uleb(results.len() as u64));
+    bytes.extend(results);
+    bytes
+}
+
+fn import(field: &str, type_index: u32) -> Vec<u8> {
+    let mut bytes = name("env");
+    bytes.extend(name(field));
+    bytes.push(0x00);
+    bytes.extend(uleb(type_index as u64));
+    bytes
+}
+
+fn export(field: &str, kind: u8, index: u32) -> Vec<u8> {
+    let mut bytes = name(field);
+    bytes.push(kind);
+    bytes.extend(uleb(index as u64));
+    bytes
+}
+
+fn global(value_type: u8, init: Code) -> Vec<u8> {
+    let mut bytes = vec![value_type, 0x01];
+    bytes.extend(init.end().0);
+    bytes
+}
+
+/// Encodes a function body with one local of each of the given types.
+fn function(locals: &[u8], code: Code) -> Vec<u8> {
+    let mut body = uleb(locals.len() as u64);
+    for &local in locals {
+        body.extend([1, local]);
+    }
+    body.extend(code.end().0);
+
+    let mut bytes = uleb(body.len() as u64);
+    bytes.extend(body);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use wasmi::{Caller, Engine, Linker, Module, Store};
+
+    use crate::{bf_parse, ir::Program, BraincluckError, CellWidth, Config, TapeMode};
+
+    use super::{super::tests::check_generator, error, generate, sleb, uleb, ERROR_OUT_OF_MEMORY};
+
+    /// The host state for running a compiled module.
+    struct Host {
+        input: Vec<u8>,
+        position: usize,
+        output: Vec<u8>,
+        error: Option<BraincluckError>,
+    }
+
+    /// Compiles `program` and runs it with `input`, returning what it wrote and the error it stopped with.
+    fn run_compiled(
+        program: &str,
+        config: &Config,
+        input: &[u8],
+    ) -> (Vec<u8>, Option<BraincluckError>) {
+        let commands = bf_parse(program).expect("parsing returned an error");
+        let wasm = generate(&Program::new(&commands), config).expect("generating should succeed");
+
+        let engine = Engine::default();
+        let module = Module::new(&engine, &wasm[..]).expect("the module should be valid");
+        let mut store = Store::new(
+            &engine,
+            Host {
+                input: input.to_vec(),
+                position: 0,
+                output: vec![],
+                error: None,
+            },
+        );
+
+        let mut linker = <Linker<Host>>::new(&engine);
+        linker
+            .func_wrap("env", "read", |mut caller: Caller<'_, Host>| {
+                let host = caller.data_mut();
+                let byte = host
+                    .input
+                    .get(host.position)
+                    .map_or(-1, |&byte| byte as i32);
+                host.position += 1;
+                byte
+            })
+            .expect("defining read should work");
+        linker
+            .func_wrap("env", "write", |mut caller: Caller<'_, Host>, byte: i32| {
+                caller.data_mut().output.push(byte as u8);
+            })
+            .expect("defining write should work");
+        linker
+            .func_wrap(
+                "env",
+                "error",
+                |mut caller: Caller<'_, Host>, kind: i32, first: i64, second: i64| {
+                    caller.data_mut().error = Some(error(kind, first, second));
+                },
+            )
+            .expect("defining error should work");
+
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .expect("instantiating should work");
+        let run = instance
+            .get_typed_func::<(), ()>(&store, "run")
+            .expect("run should be exported");
+        let result = run.call(&mut store, ());
+
+        let host = store.into_data();
+        assert_eq!(result.is_err(), host.error.is_some(), "{result:?}");
+        (host.output, host.error)
+    }
+
+    #[test]
+    fn leb128() {
+        assert_eq!(uleb(0), vec![0]);
+        assert_eq!(uleb(624485), vec![0xe5, 0x8e, 0x26]);
+        assert_eq!(sleb(-1), vec![0x7f]);
+        assert_eq!(sleb(63), vec![0x3f]);
+        assert_eq!(sleb(64), vec![0xc0, 0x00]);
+        assert_eq!(sleb(-123456), vec![0xc0, 0xbb, 0x78]);
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert!(matches!(
+            error(ERROR_OUT_OF_MEMORY, 0, 0),
+            BraincluckError::IOError(err) if err.kind() == io::ErrorKind::OutOfMemory
+        ));
+        assert!(matches!(
+            error(-1, 0, 0),
+            BraincluckError::IOError(err) if err.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+
+    /// Tapes that don't fit in a 32 bit memory should be refused rather than truncated.
+    #[test]
+    fn tape_too_long() {
+        for (cell_width, tape_mode, supported) in [
+            (CellWidth::U8, TapeMode::Bounded(i32::MAX as usize), true),
+            (CellWidth::U8, TapeMode::Bounded(1 << 31), false),
+            (CellWidth::U32, TapeMode::Circular(1 << 30), true),
+            (CellWidth::U32, TapeMode::Circular((1 << 30) + 1), false),
+            (CellWidth::I64, TapeMode::Bounded(usize::MAX), false),
+        ] {
+            let config = Config {
+                cell_width,
+                tape_mode,
+                ..Config::default()
+            };
+            let result = generate(&Program::default(), &config);
+            assert_eq!(
+                !matches!(result, Err(BraincluckError::UnsupportedConfig(_))),
+                supported,
+                "{config:?}"
+            );
+        }
+    }
+
+    /// Compiled programs should behave the same as the interpreter.
+    #[test]
+    fn matches_interpreter() {
+        check_generator(|_, program, config, input| {
+            let (out, err) = run_compiled(program, config, input);
+            (out, err.map(|err| err.to_string()))
+        });
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Deref;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
     Right,
@@ -8,3 +10,81 @@ pub enum Command {
     Input,
     Jump(Vec<Command>),
 }
+
+impl Command {
+    /// Returns how many command characters `commands` is made of, counting both brackets of every loop.
+    pub fn count_instructions(commands: &[Command]) -> usize {
+        let mut count = 0;
+        let mut blocks = vec![commands];
+        while let Some(block) = blocks.pop() {
+            for command in block {
+                match command {
+                    Command::Jump(inner) => {
+                        count += 2;
+                        blocks.push(inner);
+                    }
+                    _ => count += 1,
+                }
+            }
+        }
+
+        count
+    }
+}
+
+/// Owns a list of parsed commands, and drops nested loops with an explicit stack, as dropping a plain
+/// `Vec<Command>` recurses into every loop and can overflow the stack with deeply nested loops.
+///
+/// This dereferences to a slice of commands, so it can be passed anywhere that takes `&[Command]`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Commands(Vec<Command>);
+
+impl Commands {
+    /// Returns the commands, which are then dropped recursively again.
+    pub fn into_inner(mut self) -> Vec<Command> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl From<Vec<Command>> for Commands {
+    fn from(commands: Vec<Command>) -> Self {
+        Self(commands)
+    }
+}
+
+impl Deref for Commands {
+    type Target = [Command];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for Commands {
+    fn drop(&mut self) {
+        let mut commands = std::mem::take(&mut self.0);
+        while let Some(command) = commands.pop() {
+            if let Command::Jump(mut inner) = command {
+                commands.append(&mut inner);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Commands};
+
+    /// Loops should be able to be taken apart, which a `Drop` on [`Command`] itself would prevent.
+    #[test]
+    fn move_out_of_loop() {
+        let commands = Commands::from(vec![Command::Jump(vec![Command::Increment])]);
+
+        let inner = match commands.into_inner().pop() {
+            Some(Command::Jump(inner)) => inner,
+            command => panic!("expected a loop, got {command:?}"),
+        };
+
+        assert_eq!(inner, vec![Command::Increment]);
+    }
+}
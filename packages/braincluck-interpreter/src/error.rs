@@ -2,15 +2,137 @@ use std::num::TryFromIntError;
 
 use thiserror::Error;
 
+use crate::{Command, SourceMap, Span};
+
 /// The errors that can result while trying to interpret Brainfuck code.
 #[derive(Error, Debug)]
 pub enum BraincluckError {
-    #[error("parsing error: `{0}`")]
-    ParseError(String),
+    #[error(transparent)]
+    ParseError(#[from] ParseError),
     #[error(transparent)]
     IOError(#[from] std::io::Error),
     #[error(transparent)]
     ConversionError(#[from] TryFromIntError),
     #[error(transparent)]
     StringFormatError(#[from] std::fmt::Error),
+    #[error("tried to read input past the end of the input")]
+    UnexpectedEof,
+    #[error("pointer moved to cell {position}, outside of a tape with {length} cells")]
+    PointerOutOfBounds { position: isize, length: usize },
+    #[error("cell {index} went out of bounds while executing `{command:?}`")]
+    CellOverflow { index: usize, command: Command },
+    #[error("stopped after {steps} steps at cell {position}")]
+    StepLimitExceeded { steps: u64, position: usize },
+    #[error(transparent)]
+    Runtime(Box<RuntimeError>),
+    #[error("{0} is not supported when compiling")]
+    UnsupportedConfig(&'static str),
+}
+
+impl BraincluckError {
+    /// Returns the underlying error, without the context of a [`BraincluckError::Runtime`].
+    pub fn inner(&self) -> &BraincluckError {
+        match self {
+            BraincluckError::Runtime(err) => &err.error,
+            err => err,
+        }
+    }
+
+    /// Fills in the source location of a [`BraincluckError::Runtime`] from a [`SourceMap`] of the program
+    /// that was run.
+    pub fn with_source_map(mut self, source_map: &SourceMap) -> Self {
+        if let BraincluckError::Runtime(err) = &mut self {
+            err.span = source_map.get(err.instruction);
+        }
+
+        self
+    }
+}
+
+/// An error that happened while running a program, along with the state of the program when it happened.
+#[derive(Error, Debug)]
+#[error("{error} ({})", self.context())]
+pub struct RuntimeError {
+    #[source]
+    pub error: BraincluckError,
+    /// The position of the pointer.
+    pub index: usize,
+    /// The value of the current cell.
+    pub value: i64,
+    /// The number of steps run so far.
+    pub steps: u64,
+    /// The index of the command character that failed. See [`SourceMap`] for how commands are counted.
+    pub instruction: usize,
+    /// The location of the command that failed, if known.
+    pub span: Option<Span>,
+}
+
+impl RuntimeError {
+    /// Describes the state of the program, leaving out the cell and steps if the error already says them.
+    fn context(&self) -> String {
+        let mut parts = Vec::new();
+        match self.error {
+            BraincluckError::CellOverflow { .. } | BraincluckError::StepLimitExceeded { .. } => {
+                parts.push(format!("with value {}", self.value));
+            }
+            _ => parts.push(format!("at cell {} with value {}", self.index, self.value)),
+        }
+        if !matches!(self.error, BraincluckError::StepLimitExceeded { .. }) {
+            parts.push(format!("after {} steps", self.steps));
+        }
+        if let Some(span) = self.span {
+            parts.push(format!("at line {}, column {}", span.line, span.column));
+        }
+
+        parts.join(", ")
+    }
+}
+
+/// The errors that can result while trying to parse Brainfuck code.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("unmatched `[` at line {}, column {}", span.line, span.column)]
+    UnmatchedOpen { span: Span },
+    #[error("unexpected `]` at line {}, column {}", span.line, span.column)]
+    UnexpectedClose { span: Span },
+}
+
+impl ParseError {
+    /// Returns where the error happened.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnmatchedOpen { span } | ParseError::UnexpectedClose { span } => *span,
+        }
+    }
+
+    /// Renders the error along with the offending line of `source`, with a caret pointing at the problem.
+    /// For example:
+    ///
+    /// ```text
+    /// error: unexpected `]` at line 2, column 4
+    ///   |
+    /// 2 | +-.]
+    ///   |    ^ this `]` has no matching `[`
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let span = self.span();
+        let line = source.lines().nth(span.line - 1).unwrap_or_default();
+        let label = match self {
+            ParseError::UnmatchedOpen { .. } => "this `[` is never closed",
+            ParseError::UnexpectedClose { .. } => "this `]` has no matching `[`",
+        };
+
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let indent: String = line
+            .chars()
+            .take(span.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        format!(
+            "error: {}\n{} |\n{} | {}\n{} | {}^ {}",
+            self, gutter, number, line, gutter, indent, label
+        )
+    }
 }
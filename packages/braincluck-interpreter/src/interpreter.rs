@@ -0,0 +1,205 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    bf_parse_with_source_map, ArithmeticMode, BraincluckError, CellValue, Cells, Command,
+    EofBehaviour, OutputMode, TapeMode,
+};
+
+/// The type of the cells used by an [`Interpreter`]. This picks the [`CellValue`] that [`Cells`] is created
+/// with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellWidth {
+    /// Signed 8 bit cells.
+    I8,
+    /// Unsigned 8 bit cells.
+    #[default]
+    U8,
+    /// Unsigned 16 bit cells.
+    U16,
+    /// Unsigned 32 bit cells.
+    U32,
+    /// Signed 64 bit cells.
+    I64,
+}
+
+/// The settings used by an [`Interpreter`]. The defaults match [`Cells::default`] with 8 bit cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Config {
+    pub cell_width: CellWidth,
+    pub tape_mode: TapeMode,
+    pub eof_behaviour: EofBehaviour,
+    pub arithmetic_mode: ArithmeticMode,
+    pub output_mode: OutputMode,
+    pub step_limit: Option<u64>,
+}
+
+impl Config {
+    /// Creates [`Cells`] with these settings. The type of the cells is picked by `T`, so
+    /// [`Config::cell_width`] is ignored.
+    pub fn cells<T: CellValue>(&self) -> Cells<T> {
+        let mut cells = Cells::default()
+            .with_tape_mode(self.tape_mode)
+            .with_eof_behaviour(self.eof_behaviour)
+            .with_arithmetic_mode(self.arithmetic_mode)
+            .with_output_mode(self.output_mode);
+        cells.set_step_limit(self.step_limit);
+
+        cells
+    }
+}
+
+/// Runs Brainfuck programs with a given [`Config`], without having to set up the [`Cells`], parse the
+/// program, or deal with the input and output by hand.
+///
+/// ```
+/// use braincluck_interpreter::{EofBehaviour, Interpreter};
+///
+/// let interpreter = Interpreter::default().with_eof_behaviour(EofBehaviour::Zero);
+/// let out = interpreter.run_to_vec(",[.,]", b"Hi").unwrap();
+/// assert_eq!(out, b"Hi");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interpreter {
+    config: Config,
+}
+
+impl Interpreter {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn with_cell_width(mut self, cell_width: CellWidth) -> Self {
+        self.config.cell_width = cell_width;
+        self
+    }
+
+    pub fn with_tape_mode(mut self, tape_mode: TapeMode) -> Self {
+        self.config.tape_mode = tape_mode;
+        self
+    }
+
+    pub fn with_eof_behaviour(mut self, eof_behaviour: EofBehaviour) -> Self {
+        self.config.eof_behaviour = eof_behaviour;
+        self
+    }
+
+    pub fn with_arithmetic_mode(mut self, arithmetic_mode: ArithmeticMode) -> Self {
+        self.config.arithmetic_mode = arithmetic_mode;
+        self
+    }
+
+    pub fn with_output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.config.output_mode = output_mode;
+        self
+    }
+
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.config.step_limit = Some(step_limit);
+        self
+    }
+
+    /// Parses and runs `program` on a fresh tape. Runtime errors have the source location of the failing
+    /// command filled in.
+    pub fn run<W: Write, R: Read>(
+        &self,
+        program: &str,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        let (commands, source_map) = bf_parse_with_source_map(program)?;
+        self.run_commands(&commands, output, input)
+            .map_err(|err| err.with_source_map(&source_map))
+    }
+
+    /// Runs already parsed commands on a fresh tape.
+    pub fn run_commands<W: Write, R: Read>(
+        &self,
+        commands: &[Command],
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        match self.config.cell_width {
+            CellWidth::I8 => self.config.cells::<i8>().interpret(commands, output, input),
+            CellWidth::U8 => self.config.cells::<u8>().interpret(commands, output, input),
+            CellWidth::U16 => self
+                .config
+                .cells::<u16>()
+                .interpret(commands, output, input),
+            CellWidth::U32 => self
+                .config
+                .cells::<u32>()
+                .interpret(commands, output, input),
+            CellWidth::I64 => self
+                .config
+                .cells::<i64>()
+                .interpret(commands, output, input),
+        }
+    }
+
+    /// Parses and runs `program` with the given input, returning everything it wrote.
+    pub fn run_to_vec(&self, program: &str, input: &[u8]) -> Result<Vec<u8>, BraincluckError> {
+        let mut out = vec![];
+        self.run(program, &mut out, &mut io::Cursor::new(input))?;
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArithmeticMode, BraincluckError, EofBehaviour, OutputMode, TapeMode};
+
+    use super::{CellWidth, Config, Interpreter};
+
+    #[test]
+    fn builder() {
+        let interpreter = Interpreter::default()
+            .with_cell_width(CellWidth::U16)
+            .with_tape_mode(TapeMode::Circular(10))
+            .with_eof_behaviour(EofBehaviour::Zero)
+            .with_arithmetic_mode(ArithmeticMode::Checked)
+            .with_output_mode(OutputMode::Text)
+            .with_step_limit(100);
+
+        assert_eq!(
+            interpreter.config(),
+            &Config {
+                cell_width: CellWidth::U16,
+                tape_mode: TapeMode::Circular(10),
+                eof_behaviour: EofBehaviour::Zero,
+                arithmetic_mode: ArithmeticMode::Checked,
+                output_mode: OutputMode::Text,
+                step_limit: Some(100),
+            }
+        );
+    }
+
+    /// Runtime errors from a whole program should have a source location.
+    #[test]
+    fn source_location() {
+        let err = Interpreter::default()
+            .with_step_limit(2)
+            .run_to_vec("+\n[]", &[])
+            .expect_err("run should fail");
+
+        match err {
+            BraincluckError::Runtime(context) => assert_eq!(
+                context.span.map(|span| (span.line, span.column)),
+                Some((2, 2))
+            ),
+            err => panic!("expected a runtime error, got {err:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(matches!(
+            Interpreter::default().run_to_vec("[", &[]),
+            Err(BraincluckError::ParseError(_))
+        ));
+    }
+}
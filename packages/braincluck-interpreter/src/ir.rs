@@ -0,0 +1,600 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use crate::{ArithmeticMode, BraincluckError, CellValue, Cells, Command};
+
+/// A single operation in an optimized [`Program`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Adds a value to the current cell, folded from a run of `+` or a run of `-`.
+    Add(i64),
+    /// Moves the pointer, folded from a run of `>` or a run of `<`.
+    Move(isize),
+    Output,
+    Input,
+    /// The start of a loop, holding the index of its matching [`Op::LoopEnd`].
+    LoopStart(usize),
+    /// The end of a loop, holding the index of its matching [`Op::LoopStart`].
+    LoopEnd(usize),
+    /// Sets the current cell to zero, from a loop like `[-]` or `[+]`. Holds how much the loop changes the
+    /// cell by every iteration.
+    Clear(i64),
+    /// Moves the pointer by the given offset until it reaches a zero cell, from a loop like `[>]` or `[<]`.
+    Scan(isize),
+    /// Adds the current cell multiplied by `factor` to the cell at `offset`, from a loop like `[->++<]`. This is
+    /// always followed by an [`Op::Clear`] for the current cell.
+    MulAdd {
+        offset: isize,
+        factor: i64,
+    },
+}
+
+/// An optimized, flattened representation of a Brainfuck program.
+///
+/// Runs of the same command out of `+`, `-`, `>` and `<` are folded into a single [`Op::Add`] or [`Op::Move`].
+/// Runs that go in different directions, like `+-` or `<>`, are kept apart so they stop with the same errors on
+/// a bounded tape or with checked arithmetic. Loops are flattened into jumps between matching [`Op::LoopStart`]
+/// and [`Op::LoopEnd`]s. Common loop idioms are also replaced with a single op:
+///
+/// - Clear loops, like `[-]`, become [`Op::Clear`].
+/// - Scan loops, like `[>]`, become [`Op::Scan`].
+/// - Multiply loops, like `[->++>+++<<]`, become a [`Op::MulAdd`] for each target cell, followed by an
+///   [`Op::Clear`].
+///
+/// Every op counts as a single step towards the step limit of the [`Cells`] it is run on, except for
+/// [`Op::Scan`], which counts a step for every move. As ops are checked as a whole, an error may be reported at
+/// a different command than it would be with [`Cells::interpret`], but never at a different cell.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program {
+    ops: Vec<Op>,
+    sources: Vec<usize>,
+}
+
+impl Program {
+    /// Compiles a list of commands into a [`Program`].
+    pub fn new(commands: &[Command]) -> Self {
+        let mut ops = vec![];
+        let mut sources = vec![];
+        let mut instruction = 0;
+        let mut blocks = vec![commands.iter()];
+        let mut loop_starts = vec![];
+
+        while let Some(block) = blocks.last_mut() {
+            let command = block.next();
+            match command {
+                Some(Command::Right) => push_move(&mut ops, &mut sources, 1, instruction),
+                Some(Command::Left) => push_move(&mut ops, &mut sources, -1, instruction),
+                Some(Command::Increment) => push_add(&mut ops, &mut sources, 1, instruction),
+                Some(Command::Decrement) => push_add(&mut ops, &mut sources, -1, instruction),
+                Some(Command::Output) => {
+                    ops.push(Op::Output);
+                    sources.push(instruction);
+                }
+                Some(Command::Input) => {
+                    ops.push(Op::Input);
+                    sources.push(instruction);
+                }
+                Some(Command::Jump(inner)) => {
+                    if let Some(idiom) = idiom(inner) {
+                        sources.resize(sources.len() + idiom.len(), instruction);
+                        ops.extend(idiom);
+                        instruction += Command::count_instructions(inner) + 2;
+                        continue;
+                    }
+
+                    loop_starts.push(ops.len());
+                    ops.push(Op::LoopStart(0));
+                    sources.push(instruction);
+                    blocks.push(inner.iter());
+                }
+                None => {
+                    blocks.pop();
+                    if let Some(start) = loop_starts.pop() {
+                        ops[start] = Op::LoopStart(ops.len());
+                        ops.push(Op::LoopEnd(start));
+                        sources.push(instruction);
+                    } else {
+                        continue;
+                    }
+                }
+            }
+            instruction += 1;
+        }
+
+        Self { ops, sources }
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// Returns the index of the first command character that each op was compiled from. See
+    /// [`SourceMap`](crate::SourceMap) for how commands are counted.
+    pub fn sources(&self) -> &[usize] {
+        &self.sources
+    }
+
+    /// Runs the program on `cells`.
+    ///
+    /// If an op fails, the error is returned as a [`BraincluckError::Runtime`] pointing at the first command
+    /// the op was compiled from.
+    pub fn run<T: CellValue, W: Write, R: Read>(
+        &self,
+        cells: &mut Cells<T>,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        let mut pc = 0;
+        self.run_from(&mut pc, cells, output, input)
+            .map_err(|err| cells.runtime_error(err, self.sources[pc]))
+    }
+
+    fn run_from<T: CellValue, W: Write, R: Read>(
+        &self,
+        pc: &mut usize,
+        cells: &mut Cells<T>,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        while let Some(op) = self.ops.get(*pc) {
+            cells.count_step()?;
+            match *op {
+                Op::Add(delta) => cells.add(delta)?,
+                Op::Move(offset) => cells.move_by(offset)?,
+                Op::Output => cells.write_output(output)?,
+                Op::Input => cells.read_input(input)?,
+                Op::LoopStart(end) => {
+                    if cells.is_current_cell_zero() {
+                        *pc = end;
+                    }
+                }
+                Op::LoopEnd(start) => {
+                    if !cells.is_current_cell_zero() {
+                        *pc = start;
+                    }
+                }
+                Op::Clear(step) => clear(cells, step)?,
+                Op::Scan(offset) => {
+                    while !cells.is_current_cell_zero() {
+                        cells.count_step()?;
+                        cells.move_by(offset)?;
+                    }
+                }
+                Op::MulAdd { offset, factor } => mul_add(cells, offset, factor)?,
+            }
+            *pc += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the ops for the loop with the body `block` if it is a known idiom.
+fn idiom(block: &[Command]) -> Option<Vec<Op>> {
+    let mut position = 0;
+    let mut deltas = BTreeMap::new();
+    for command in block {
+        match command {
+            Command::Right => position += 1,
+            Command::Left => position -= 1,
+            Command::Increment => *deltas.entry(position).or_insert(0) += 1,
+            Command::Decrement => *deltas.entry(position).or_insert(0) -= 1,
+            _ => return None,
+        }
+    }
+    deltas.retain(|_, delta| *delta != 0);
+
+    if deltas.is_empty() {
+        let is_scan = !block.is_empty()
+            && (block.iter().all(|command| *command == Command::Right)
+                || block.iter().all(|command| *command == Command::Left));
+        return is_scan.then(|| vec![Op::Scan(position)]);
+    }
+
+    let step = match deltas.remove(&0) {
+        Some(step @ (-1 | 1)) if position == 0 => step,
+        _ => return None,
+    };
+
+    // Each iteration adds `delta` to the target, and the loop runs `-current / step` times.
+    let mut ops: Vec<Op> = deltas
+        .into_iter()
+        .map(|(offset, delta)| Op::MulAdd {
+            offset,
+            factor: -step * delta,
+        })
+        .collect();
+    ops.push(Op::Clear(step));
+
+    Some(ops)
+}
+
+/// Runs an [`Op::Clear`], returning an error if the loop it replaces would overflow with checked arithmetic.
+pub(crate) fn clear<T: CellValue>(cells: &mut Cells<T>, step: i64) -> Result<(), BraincluckError> {
+    let value = cells.current().to_i64();
+    if cells.arithmetic_mode() == ArithmeticMode::Checked && value.signum() == step.signum() {
+        return Err(BraincluckError::CellOverflow {
+            index: cells.index(),
+            command: if step > 0 {
+                Command::Increment
+            } else {
+                Command::Decrement
+            },
+        });
+    }
+
+    cells.input(T::default());
+    Ok(())
+}
+
+/// Runs an [`Op::MulAdd`], leaving the pointer where it started.
+pub(crate) fn mul_add<T: CellValue>(
+    cells: &mut Cells<T>,
+    offset: isize,
+    factor: i64,
+) -> Result<(), BraincluckError> {
+    let value = cells.current().to_i64();
+    if value == 0 {
+        // The loop wouldn't have run at all, so this must not touch the target cell.
+        return Ok(());
+    }
+
+    let delta = match cells.arithmetic_mode() {
+        ArithmeticMode::Wrapping => value.wrapping_mul(factor),
+        ArithmeticMode::Checked => {
+            // If this overflows an `i64`, it's guaranteed to overflow the target cell too.
+            value
+                .checked_mul(factor)
+                .unwrap_or(if (value > 0) == (factor > 0) {
+                    i64::MAX
+                } else {
+                    i64::MIN
+                })
+        }
+    };
+
+    cells.move_by(offset)?;
+    let result = cells.add(delta);
+    cells.move_by(-offset)?;
+
+    result
+}
+
+/// Pushes an [`Op::Add`], folding it into the previous op if that goes the same way.
+fn push_add(ops: &mut Vec<Op>, sources: &mut Vec<usize>, delta: i64, instruction: usize) {
+    match ops.last_mut() {
+        Some(Op::Add(previous)) if previous.signum() == delta.signum() => *previous += delta,
+        _ => {
+            ops.push(Op::Add(delta));
+            sources.push(instruction);
+        }
+    }
+}
+
+/// Pushes an [`Op::Move`], folding it into the previous op if that goes the same way.
+fn push_move(ops: &mut Vec<Op>, sources: &mut Vec<usize>, offset: isize, instruction: usize) {
+    match ops.last_mut() {
+        Some(Op::Move(previous)) if previous.signum() == offset.signum() => *previous += offset,
+        _ => {
+            ops.push(Op::Move(offset));
+            sources.push(instruction);
+        }
+    }
+}
+
+/// All these tests are based on code from the [Esolang wiki page](https://esolangs.org/wiki/Brainfuck) on the language.
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        bf_parse, ArithmeticMode, BraincluckError, CellValue, Cells, EofBehaviour, TapeMode,
+    };
+
+    use super::{Op, Program};
+
+    /// Runs `program` through both [`Cells::interpret`] and [`Program::run`], checking that the outputs
+    /// match before returning it.
+    fn run_bytes<T: CellValue>(program: &str, cells: Cells<T>, input: &[u8]) -> Vec<u8> {
+        let commands = bf_parse(program).expect("parsing returned an error");
+
+        let mut expected = vec![];
+        cells
+            .clone()
+            .interpret(&commands, &mut expected, &mut Cursor::new(input.to_vec()))
+            .expect("interpret should succeed");
+
+        let mut out = vec![];
+        Program::new(&commands)
+            .run(
+                &mut cells.clone(),
+                &mut out,
+                &mut Cursor::new(input.to_vec()),
+            )
+            .expect("run should succeed");
+
+        assert_eq!(out, expected, "outputs should match");
+        out
+    }
+
+    /// Like [`run_bytes`], but returns the output as a string.
+    fn run<T: CellValue>(program: &str, cells: Cells<T>, input: &[u8]) -> String {
+        String::from_utf8(run_bytes(program, cells, input)).expect("string should be valid utf8")
+    }
+
+    #[test]
+    fn folding() {
+        let commands = bf_parse("+++-->><<<[-]+-,.").expect("parsing returned an error");
+
+        assert_eq!(
+            Program::new(&commands).ops(),
+            &[
+                Op::Add(3),
+                Op::Add(-2),
+                Op::Move(2),
+                Op::Move(-3),
+                Op::Clear(-1),
+                Op::Add(1),
+                Op::Add(-1),
+                Op::Input,
+                Op::Output
+            ]
+        );
+        assert_eq!(
+            Program::new(&commands).sources(),
+            &[0, 3, 5, 7, 10, 13, 14, 15, 16]
+        );
+    }
+
+    #[test]
+    fn hello_world() {
+        let program = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        assert_eq!(run(program, Cells::<u8>::default(), &[]), "Hello World!\n");
+    }
+
+    #[test]
+    fn tricky_hello_world() {
+        let program = ">++++++++[-<+++++++++>]<.>>+>-[+]++>++>+++[>[->+++<<+++>]<<]>-----.>->
+        +++..+++.>-.<<+[>[+>+]>>]<--------------.>>.+++.------.--------.>+.>+.";
+        assert_eq!(run(program, Cells::<u8>::default(), &[]), "Hello World!\n");
+    }
+
+    #[test]
+    fn wrapping_hello_world() {
+        let program =
+            "--<-<<+[+[<+>--->->->-<<<]>]<<--.<++++++.<<-..<<.<+.>>.>>.<<<.+++.>>.>>-.<<<+.";
+        assert_eq!(run(program, Cells::<u8>::default(), &[]), "Hello, World!");
+    }
+
+    #[test]
+    fn short_hello_world() {
+        let program = "+[-->-[>>+>-----<<]<--<---]>-.>>>+.>>..+++[.>]<<<<.+++.------.<<-.>>>>+.";
+        assert_eq!(run(program, Cells::<u8>::default(), &[]), "Hello, World!");
+    }
+
+    #[test]
+    fn cat() {
+        let cells = Cells::<u8>::default().with_eof_behaviour(EofBehaviour::Zero);
+        assert_eq!(run(",[.,]", cells, b"Hello!"), "Hello!");
+    }
+
+    #[test]
+    fn wide_cells() {
+        let program = "++++++++[>++++++++<-]>[<++++>-]+<[>-<[>++++<-]>[<++++++++>-]<[>++++++++<-]+>[>++++++++++[>+++++<-]>+.-.[-]<<[-]<->]<[>>+++++++[>+++++++<-]>.+++++.[-]<<<-]]>[>++++++++[>+++++++<-]>.[-]<<-]<";
+        assert_eq!(run(program, Cells::<u8>::default(), &[]), "8");
+        assert_eq!(run(program, Cells::<u16>::default(), &[]), "16");
+        assert_eq!(run(program, Cells::<u32>::default(), &[]), "32");
+    }
+
+    /// Folded moves should be able to grow the tape by more than one cell at a time.
+    #[test]
+    fn grow_tape() {
+        let commands = bf_parse("+<<<+>>>>>>+").expect("parsing returned an error");
+        let mut cells: Cells = Cells::default();
+
+        Program::new(&commands)
+            .run(&mut cells, &mut vec![], &mut Cursor::new(vec![]))
+            .expect("run should succeed");
+
+        assert_eq!(cells.cells(), [1, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(cells.index(), 6);
+    }
+
+    #[test]
+    fn loops() {
+        let commands = bf_parse("+[>+.<-]").expect("parsing returned an error");
+
+        assert_eq!(
+            Program::new(&commands).ops(),
+            &[
+                Op::Add(1),
+                Op::LoopStart(7),
+                Op::Move(1),
+                Op::Add(1),
+                Op::Output,
+                Op::Move(-1),
+                Op::Add(-1),
+                Op::LoopEnd(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn idioms() {
+        let commands =
+            bf_parse("[-][+][>][<<][->++>+++<<][+<-->][-<>]").expect("parsing returned an error");
+
+        assert_eq!(
+            Program::new(&commands).ops(),
+            &[
+                Op::Clear(-1),
+                Op::Clear(1),
+                Op::Scan(1),
+                Op::Scan(-2),
+                Op::MulAdd {
+                    offset: 1,
+                    factor: 2
+                },
+                Op::MulAdd {
+                    offset: 2,
+                    factor: 3
+                },
+                Op::Clear(-1),
+                Op::MulAdd {
+                    offset: -1,
+                    factor: 2
+                },
+                Op::Clear(1),
+                Op::Clear(-1),
+            ]
+        );
+    }
+
+    /// Loops that look like idioms but aren't should be left alone.
+    #[test]
+    fn not_idioms() {
+        for program in ["[--]", "[>-]", "[-<]", "[><]", "[-.]", "[-[-]]", "[]"] {
+            let commands = bf_parse(program).expect("parsing returned an error");
+            let ops = Program::new(&commands);
+
+            assert!(
+                matches!(ops.ops().first(), Some(Op::LoopStart(_))),
+                "{} should stay as a loop",
+                program
+            );
+        }
+    }
+
+    /// Multiply loops should wrap the same way as the loops they replace.
+    #[test]
+    fn multiply_wrapping() {
+        let program = "+++[+>+<]>.>---[->>+++<<]>>.";
+        assert_eq!(
+            run_bytes(program, Cells::<u8>::default(), &[]),
+            vec![253, 247]
+        );
+    }
+
+    /// Multiply loops should not touch their target cells if they don't run.
+    #[test]
+    fn multiply_skipped() {
+        let commands = bf_parse("[-<+>]+").expect("parsing returned an error");
+        let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Bounded(10));
+
+        Program::new(&commands)
+            .run(&mut cells, &mut vec![], &mut Cursor::new(vec![]))
+            .expect("run should succeed");
+
+        assert_eq!(cells.current(), 1);
+    }
+
+    /// Idioms should still return errors with checked arithmetic.
+    #[test]
+    fn checked_idioms() {
+        for program in ["+[+]", "-[-]", "-[->+<]", "+>++[-<+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++>]"] {
+            let commands = bf_parse(program).expect("parsing returned an error");
+            let mut cells: Cells<i8> =
+                Cells::default().with_arithmetic_mode(ArithmeticMode::Checked);
+
+            let result =
+                Program::new(&commands).run(&mut cells, &mut vec![], &mut Cursor::new(vec![]));
+
+            assert!(
+                matches!(result.as_ref().map_err(BraincluckError::inner), Err(BraincluckError::CellOverflow { .. })),
+                "{} should overflow",
+                program
+            );
+        }
+    }
+
+    /// Folded runs should stop with the same errors as the commands they replace.
+    #[test]
+    fn folded_errors() {
+        let bounded = || Cells::<u8>::default().with_tape_mode(TapeMode::Bounded(2));
+        let checked = || Cells::<u8>::default().with_arithmetic_mode(ArithmeticMode::Checked);
+        for (program, cells) in [
+            ("<>", bounded()),
+            (">>>", bounded()),
+            (">><", bounded()),
+            ("<<<", bounded()),
+            ("+[->>+<<]", bounded()),
+            ("-+", checked()),
+            ("--", checked()),
+        ] {
+            let commands = bf_parse(program).expect("parsing returned an error");
+            let expected = cells
+                .clone()
+                .interpret(&commands, &mut vec![], &mut Cursor::new(vec![]))
+                .expect_err("interpret should fail");
+            let err = Program::new(&commands)
+                .run(&mut cells.clone(), &mut vec![], &mut Cursor::new(vec![]))
+                .expect_err("run should fail");
+
+            assert_eq!(
+                err.inner().to_string(),
+                expected.inner().to_string(),
+                "{program}"
+            );
+        }
+    }
+
+    /// Errors should point at the first command of the op that failed.
+    #[test]
+    fn runtime_error() {
+        for (program, instruction) in [("+[>+]", 2), ("+[-<+>]", 1)] {
+            let commands = bf_parse(program).expect("parsing returned an error");
+            let mut cells: Cells = Cells::default().with_tape_mode(TapeMode::Bounded(3));
+
+            match Program::new(&commands).run(&mut cells, &mut vec![], &mut Cursor::new(vec![])) {
+                Err(BraincluckError::Runtime(context)) => {
+                    assert_eq!(context.instruction, instruction, "{program}")
+                }
+                result => panic!("expected a runtime error, got {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn scan() {
+        let commands = bf_parse("+>+>+>>+<<<<[>]>[>]").expect("parsing returned an error");
+        let mut cells: Cells = Cells::default();
+
+        Program::new(&commands)
+            .run(&mut cells, &mut vec![], &mut Cursor::new(vec![]))
+            .expect("run should succeed");
+
+        assert_eq!(cells.index(), 5);
+    }
+
+    #[test]
+    fn cell_size() {
+        let program = r##"
+        ++++++++[>++++++++<-]>[<++++>-]
+        +<[>-<
+            [>++++<-]>[<++++++++>-]<[>++++++++<-]
+            +>[>
+                ++++++++++[>+++++<-]>+.-.[-]<
+            <[-]<->] <[>>
+                +++++++[>+++++++<-]>.+++++.[-]<
+        <<-]] >[>
+            ++++++++[>+++++++<-]>.[-]<
+        <-]<
+        +++++++++++[>+++>+++++++++>+++++++++>+<<<<-]>-.>-.+++++++.+++++++++++.<.
+        >>.++.+++++++..<-.>>-.
+        [[-]<]
+        "##;
+        assert_eq!(run(program, Cells::<u8>::default(), &[]), "8 bit cells\n");
+        assert_eq!(run(program, Cells::<u16>::default(), &[]), "16 bit cells\n");
+        assert_eq!(run(program, Cells::<u32>::default(), &[]), "32 bit cells\n");
+        assert_eq!(run(program, Cells::<i64>::default(), &[]), "32 bit cells\n");
+    }
+
+    /// Deeply nested loops should not overflow the stack.
+    #[test]
+    fn deeply_nested() {
+        let program = format!("+{}-{}.", "[".repeat(100_000), "]".repeat(100_000));
+        assert_eq!(run_bytes(&program, Cells::<u8>::default(), &[]), vec![0]);
+    }
+}
@@ -0,0 +1,717 @@
+//! A JIT compiler that turns an optimized [`Program`] into x86-64 machine code, for programs that run for too
+//! long to interpret. This needs the `jit` feature, and only works on x86-64 Linux.
+//!
+//! The machine code works directly on the tape of a [`Cells`], and handles the common cases of each op itself,
+//! including moving into the spare room the [`Cells`] keep on either side of the tape. Everything else (I/O,
+//! making more room or wrapping the tape, checked arithmetic, and errors) calls back into the same [`Cells`]
+//! methods used by [`Program::run`], so the results are the same.
+
+use std::{
+    ffi::c_void,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    ptr,
+};
+
+use crate::{
+    ir::{self, Op, Program},
+    x86::{Assembler, JAE, JBE, JE, JMP, JNE, RAX, RCX},
+    ArithmeticMode, BraincluckError, CellValue, Cells,
+};
+
+/// A [`Program`] compiled to machine code for cells of type `T`.
+///
+/// ```
+/// use braincluck_interpreter::{bf_parse, ir::Program, jit::Jit, Cells};
+///
+/// let commands = bf_parse("++++++++[>++++++++<-]>+.").unwrap();
+/// let jit: Jit = Jit::new(&Program::new(&commands)).unwrap();
+///
+/// let mut out = vec![];
+/// jit.run(&mut Cells::default(), &mut out, &mut std::io::empty()).unwrap();
+/// assert_eq!(out, b"A");
+/// ```
+#[derive(Debug)]
+pub struct Jit<T: CellValue = u8> {
+    code: ExecutableMemory,
+    sources: Vec<usize>,
+    cell: PhantomData<fn(T) -> T>,
+}
+
+impl<T: CellValue> Jit<T> {
+    /// Compiles `program`. This only fails if the memory for the machine code can't be set up.
+    pub fn new(program: &Program) -> Result<Self, BraincluckError> {
+        Ok(Self {
+            code: ExecutableMemory::new(&compile(program, T::BITS / 8))?,
+            sources: program.sources().to_vec(),
+            cell: PhantomData,
+        })
+    }
+
+    /// Runs the program on `cells`, following all of its settings, the same as [`Program::run`].
+    ///
+    /// If an op fails, the error is returned as a [`BraincluckError::Runtime`] pointing at the first command
+    /// the op was compiled from.
+    ///
+    /// # Panics
+    ///
+    /// `output` and `input` are called from inside the machine code, so if they panic, the process aborts.
+    pub fn run<W: Write, R: Read>(
+        &self,
+        cells: &mut Cells<T>,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        let mut context = Context {
+            tape: ptr::null_mut(),
+            length: 0,
+            index: 0,
+            start: 0,
+            end: 0,
+            steps: cells.steps(),
+            step_limit: cells.step_limit().unwrap_or(u64::MAX),
+            checked: (cells.arithmetic_mode() == ArithmeticMode::Checked) as u64,
+            op: 0,
+            callbacks: [
+                move_by::<T, W, R> as Callback<T, W, R>,
+                add::<T, W, R> as Callback<T, W, R>,
+                clear::<T, W, R> as Callback<T, W, R>,
+                mul_add::<T, W, R> as Callback<T, W, R>,
+                write_output::<T, W, R> as Callback<T, W, R>,
+                read_input::<T, W, R> as Callback<T, W, R>,
+            ],
+            cells,
+            output,
+            input,
+            error: None,
+        };
+        context.load();
+
+        // SAFETY: The machine code was generated by `compile` for cells of type `T`, and only touches the tape
+        // within `context.length` cells of `context.tape`, and the fields of `context` at the offsets below.
+        let status = unsafe {
+            let function: unsafe extern "C" fn(*mut c_void) -> u64 =
+                std::mem::transmute(self.code.pointer);
+            function((&mut context as *mut Context<'_, T, W, R>).cast())
+        };
+
+        let Context {
+            index,
+            start,
+            end,
+            steps,
+            op,
+            cells,
+            error,
+            ..
+        } = context;
+        cells.set_steps(steps);
+        let error = match status {
+            DONE => {
+                cells.set_memory_position(start, end, index);
+                return Ok(());
+            }
+            STEP_LIMIT_EXCEEDED => {
+                cells.set_memory_position(start, end, index);
+                BraincluckError::StepLimitExceeded {
+                    steps,
+                    position: cells.index(),
+                }
+            }
+            // The failing callback has already left the cells as they were when the error happened.
+            _ => error.expect("a failed callback should set an error"),
+        };
+
+        Err(cells.runtime_error(error, self.sources[op]))
+    }
+}
+
+// What the machine code returns.
+const DONE: u64 = 0;
+const FAILED: u64 = 1;
+const STEP_LIMIT_EXCEEDED: u64 = 2;
+
+// The offsets of the fields of `Context` that the machine code uses.
+const TAPE: u8 = 0;
+const LENGTH: u8 = 8;
+const INDEX: u8 = 16;
+const START: u8 = 24;
+const END: u8 = 32;
+const STEPS: u8 = 40;
+const STEP_LIMIT: u8 = 48;
+const CHECKED: u8 = 56;
+const OP: u8 = 64;
+const CALLBACKS: u8 = 72;
+
+// The order of the callbacks in `Context::callbacks`.
+const MOVE_BY: u8 = 0;
+const ADD: u8 = 1;
+const CLEAR: u8 = 2;
+const MUL_ADD: u8 = 3;
+const OUTPUT: u8 = 4;
+const INPUT: u8 = 5;
+
+/// A function the machine code calls for anything it doesn't handle itself. It returns [`DONE`] if the
+/// machine code can carry on, or [`FAILED`] after setting [`Context::error`].
+type Callback<'a, T, W, R> = extern "C" fn(&mut Context<'a, T, W, R>, i64, i64) -> u64;
+
+/// The state shared between [`Jit::run`] and the machine code, which is passed a pointer to it.
+///
+/// While the machine code runs, it keeps the tape, length, index, and steps in registers, and only writes them
+/// back here before calling a callback or returning.
+///
+/// `tape` points at all the memory of the cells, including the zeroed cells on either side of the tape, and the
+/// indexes are all from there.
+#[repr(C)]
+struct Context<'a, T: CellValue, W, R> {
+    tape: *mut T,
+    /// How many cells can be accessed from `tape`.
+    length: usize,
+    index: usize,
+    /// Where the tape of the cells starts, which the machine code moves down as the pointer moves past it.
+    start: usize,
+    /// Where the tape of the cells ends, which the machine code moves up as the pointer moves past it.
+    end: usize,
+    steps: u64,
+    /// The step limit, or [`u64::MAX`] if there isn't one.
+    step_limit: u64,
+    /// Whether to use checked arithmetic, which is always done by a callback.
+    checked: u64,
+    /// The index of the op that last called a callback or went over the step limit.
+    op: usize,
+    callbacks: [Callback<'a, T, W, R>; 6],
+    cells: &'a mut Cells<T>,
+    output: &'a mut W,
+    input: &'a mut R,
+    error: Option<BraincluckError>,
+}
+
+impl<T: CellValue, W: Write, R: Read> Context<'_, T, W, R> {
+    /// Points the machine code at the tape of the cells, which may have moved.
+    fn load(&mut self) {
+        let (memory, start, end, index) = self.cells.memory_mut();
+        self.tape = memory.as_mut_ptr();
+        self.length = memory.len();
+        self.start = start;
+        self.end = end;
+        self.index = index;
+    }
+
+    /// Runs `f` on the cells, at the position the machine code left the pointer at.
+    fn call(
+        &mut self,
+        f: impl FnOnce(&mut Cells<T>, &mut W, &mut R) -> Result<(), BraincluckError>,
+    ) -> u64 {
+        self.cells
+            .set_memory_position(self.start, self.end, self.index);
+        let result = f(self.cells, self.output, self.input);
+        self.load();
+
+        match result {
+            Ok(()) => DONE,
+            Err(err) => {
+                self.error = Some(err);
+                FAILED
+            }
+        }
+    }
+}
+
+extern "C" fn move_by<T: CellValue, W: Write, R: Read>(
+    context: &mut Context<'_, T, W, R>,
+    offset: i64,
+    _: i64,
+) -> u64 {
+    context.call(|cells, _, _| cells.move_by(offset as isize))
+}
+
+extern "C" fn add<T: CellValue, W: Write, R: Read>(
+    context: &mut Context<'_, T, W, R>,
+    delta: i64,
+    _: i64,
+) -> u64 {
+    context.call(|cells, _, _| cells.add(delta))
+}
+
+extern "C" fn clear<T: CellValue, W: Write, R: Read>(
+    context: &mut Context<'_, T, W, R>,
+    step: i64,
+    _: i64,
+) -> u64 {
+    context.call(|cells, _, _| ir::clear(cells, step))
+}
+
+extern "C" fn mul_add<T: CellValue, W: Write, R: Read>(
+    context: &mut Context<'_, T, W, R>,
+    offset: i64,
+    factor: i64,
+) -> u64 {
+    context.call(|cells, _, _| ir::mul_add(cells, offset as isize, factor))
+}
+
+extern "C" fn write_output<T: CellValue, W: Write, R: Read>(
+    context: &mut Context<'_, T, W, R>,
+    _: i64,
+    _: i64,
+) -> u64 {
+    context.call(|cells, output, _| cells.write_output(output))
+}
+
+extern "C" fn read_input<T: CellValue, W: Write, R: Read>(
+    context: &mut Context<'_, T, W, R>,
+    _: i64,
+    _: i64,
+) -> u64 {
+    context.call(|cells, _, input| cells.read_input(input))
+}
+
+/// Pages of memory holding machine code, which can be run but not written to.
+#[derive(Debug)]
+struct ExecutableMemory {
+    pointer: *mut c_void,
+    length: usize,
+}
+
+// SAFETY: The memory is never written to after it's set up.
+unsafe impl Send for ExecutableMemory {}
+unsafe impl Sync for ExecutableMemory {}
+
+impl ExecutableMemory {
+    fn new(code: &[u8]) -> io::Result<Self> {
+        let length = code.len();
+
+        // SAFETY: This maps fresh memory, which is only written to within its length, and then made executable.
+        unsafe {
+            let pointer = libc::mmap(
+                ptr::null_mut(),
+                length,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if pointer == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            let memory = Self { pointer, length };
+            ptr::copy_nonoverlapping(code.as_ptr(), pointer.cast(), length);
+            if libc::mprotect(pointer, length, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(memory)
+        }
+    }
+}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        // SAFETY: The memory was mapped in `new`, and nothing points into it once the `Jit` is gone.
+        unsafe {
+            libc::munmap(self.pointer, self.length);
+        }
+    }
+}
+
+/// Compiles `program` into a function that takes a pointer to a [`Context`] and returns [`DONE`], [`FAILED`],
+/// or [`STEP_LIMIT_EXCEEDED`]. `width` is the size of a cell in bytes.
+///
+/// While running, rbx holds the context, r12 the tape, r13 the index, r14 the length, and r15 the number of
+/// steps.
+fn compile(program: &Program, width: u32) -> Vec<u8> {
+    let mut compiler = Compiler {
+        asm: Assembler::new(width),
+        exits: vec![],
+        step_limits: vec![],
+    };
+    let asm = &mut compiler.asm;
+
+    // Save the callee-saved registers (which also aligns the stack for calls), and load the context.
+    asm.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]); // push rbx, r12, r13, r14, r15
+    asm.emit(&[0x48, 0x89, 0xfb]); // mov rbx, rdi
+    asm.emit(&[0x4c, 0x8b, 0x63, TAPE]); // mov r12, [rbx + TAPE]
+    asm.emit(&[0x4c, 0x8b, 0x73, LENGTH]); // mov r14, [rbx + LENGTH]
+    asm.emit(&[0x4c, 0x8b, 0x6b, INDEX]); // mov r13, [rbx + INDEX]
+    asm.emit(&[0x4c, 0x8b, 0x7b, STEPS]); // mov r15, [rbx + STEPS]
+
+    let mut loops = vec![];
+    for (index, op) in program.ops().iter().enumerate() {
+        compiler.step(index);
+        match *op {
+            Op::Add(delta) => {
+                let asm = &mut compiler.asm;
+                asm.emit(&[0x48, 0x83, 0x7b, CHECKED, 0]); // cmp qword [rbx + CHECKED], 0
+                let checked = asm.jump(JNE);
+                asm.add_cell(delta);
+                let done = asm.jump(JMP);
+                asm.patch_here(checked);
+                compiler.callback(index, ADD, delta, 0);
+                compiler.asm.patch_here(done);
+            }
+            Op::Move(offset) => compiler.move_by(index, offset),
+            Op::Output => compiler.callback(index, OUTPUT, 0, 0),
+            Op::Input => compiler.callback(index, INPUT, 0, 0),
+            Op::LoopStart(_) => {
+                compiler.asm.compare_cell_to_zero();
+                let end = compiler.asm.jump(JE);
+                loops.push((compiler.asm.here(), end));
+            }
+            Op::LoopEnd(_) => {
+                let (body, end) = loops.pop().expect("loops should be balanced");
+                compiler.asm.compare_cell_to_zero();
+                compiler.asm.jump_to(JNE, body);
+                compiler.asm.patch_here(end);
+            }
+            Op::Clear(step) => {
+                let asm = &mut compiler.asm;
+                asm.emit(&[0x48, 0x83, 0x7b, CHECKED, 0]); // cmp qword [rbx + CHECKED], 0
+                let checked = asm.jump(JNE);
+                asm.clear_cell();
+                let done = asm.jump(JMP);
+                asm.patch_here(checked);
+                compiler.callback(index, CLEAR, step, 0);
+                compiler.asm.patch_here(done);
+            }
+            Op::Scan(offset) => {
+                let start = compiler.asm.here();
+                compiler.asm.compare_cell_to_zero();
+                let end = compiler.asm.jump(JE);
+                compiler.step(index);
+                compiler.move_by(index, offset);
+                compiler.asm.jump_to(JMP, start);
+                compiler.asm.patch_here(end);
+            }
+            Op::MulAdd { offset, factor } => compiler.mul_add(index, offset, factor),
+        }
+    }
+
+    let Compiler {
+        mut asm,
+        exits,
+        step_limits,
+    } = compiler;
+    asm.emit(&[0x31, 0xc0]); // xor eax, eax
+    let exit = asm.here();
+    for jump in exits {
+        asm.patch(jump, exit);
+    }
+    asm.emit(&[0x4c, 0x89, 0x6b, INDEX]); // mov [rbx + INDEX], r13
+    asm.emit(&[0x4c, 0x89, 0x7b, STEPS]); // mov [rbx + STEPS], r15
+    asm.emit(&[0x41, 0x5f, 0x41, 0x5e, 0x41, 0x5d, 0x41, 0x5c, 0x5b]); // pop r15, r14, r13, r12, rbx
+    asm.emit(&[0xc3]); // ret
+
+    // The step that went over the limit wasn't actually taken.
+    let step_limit = asm.here();
+    for jump in step_limits {
+        asm.patch(jump, step_limit);
+    }
+    asm.emit(&[0x49, 0xff, 0xcf]); // dec r15
+    asm.emit(&[0xb8]); // mov eax, STEP_LIMIT_EXCEEDED
+    asm.emit(&(STEP_LIMIT_EXCEEDED as u32).to_le_bytes());
+    asm.jump_to(JMP, exit);
+
+    asm.code
+}
+
+/// Keeps track of the jumps out of the compiled ops while [`compile`] runs.
+struct Compiler {
+    asm: Assembler,
+    /// Jumps to the code that returns from the function.
+    exits: Vec<usize>,
+    /// Jumps to the code that returns [`STEP_LIMIT_EXCEEDED`].
+    step_limits: Vec<usize>,
+}
+
+impl Compiler {
+    /// Counts a step for the op at `index`, stopping if this goes over the step limit.
+    fn step(&mut self, index: usize) {
+        self.asm.emit(&[0x49, 0xff, 0xc7]); // inc r15
+        self.asm.emit(&[0x4c, 0x3b, 0x7b, STEP_LIMIT]); // cmp r15, [rbx + STEP_LIMIT]
+        let ok = self.asm.jump(JBE);
+        self.set_op(index);
+        let jump = self.asm.jump(JMP);
+        self.step_limits.push(jump);
+        self.asm.patch_here(ok);
+    }
+
+    fn set_op(&mut self, index: usize) {
+        self.asm.emit(&[0x48, 0xc7, 0x43, OP]); // mov qword [rbx + OP], index
+        self.asm.emit(&(index as u32).to_le_bytes());
+    }
+
+    /// Calls a callback with two arguments for the op at `index`, returning if it fails.
+    fn callback(&mut self, index: usize, callback: u8, first: i64, second: i64) {
+        self.asm.emit(&[0x4c, 0x89, 0x6b, INDEX]); // mov [rbx + INDEX], r13
+        self.set_op(index);
+        let asm = &mut self.asm;
+        asm.emit(&[0x48, 0x89, 0xdf]); // mov rdi, rbx
+        asm.emit(&[0x48, 0xbe]); // mov rsi, first
+        asm.emit(&first.to_le_bytes());
+        asm.emit(&[0x48, 0xba]); // mov rdx, second
+        asm.emit(&second.to_le_bytes());
+        asm.emit(&[0xff, 0x53, CALLBACKS + callback * 8]); // call [rbx + CALLBACKS + callback * 8]
+        asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+        let jump = asm.jump(JNE);
+        self.exits.push(jump);
+
+        // The callback may have moved or grown the tape.
+        let asm = &mut self.asm;
+        asm.emit(&[0x4c, 0x8b, 0x63, TAPE]); // mov r12, [rbx + TAPE]
+        asm.emit(&[0x4c, 0x8b, 0x73, LENGTH]); // mov r14, [rbx + LENGTH]
+        asm.emit(&[0x4c, 0x8b, 0x6b, INDEX]); // mov r13, [rbx + INDEX]
+    }
+
+    /// Moves the pointer, calling back to [`Cells::move_by`] if it goes past the memory of the cells.
+    fn move_by(&mut self, index: usize, offset: isize) {
+        let Ok(displacement) = i32::try_from(offset) else {
+            self.callback(index, MOVE_BY, offset as i64, 0);
+            return;
+        };
+
+        let asm = &mut self.asm;
+        asm.emit(&[0x49, 0x8d, 0x85]); // lea rax, [r13 + offset]
+        asm.emit(&displacement.to_le_bytes());
+        asm.emit(&[0x4c, 0x39, 0xf0]); // cmp rax, r14
+        let outside = asm.jump(JAE);
+        asm.emit(&[0x49, 0x89, 0xc5]); // mov r13, rax
+        self.widen(RAX, offset);
+        let done = self.asm.jump(JMP);
+        self.asm.patch_here(outside);
+        self.callback(index, MOVE_BY, offset as i64, 0);
+        self.asm.patch_here(done);
+    }
+
+    /// Widens the tape to reach the index in `reg`, which is `offset` away from the pointer and within the memory
+    /// of the cells. Uses rdx.
+    fn widen(&mut self, reg: u8, offset: isize) {
+        let asm = &mut self.asm;
+        let skip = if offset < 0 {
+            asm.emit(&[0x48, 0x3b, 0x43 | reg << 3, START]); // cmp reg, [rbx + START]
+            let skip = asm.jump(JAE);
+            asm.emit(&[0x48, 0x89, 0x43 | reg << 3, START]); // mov [rbx + START], reg
+            skip
+        } else {
+            asm.emit(&[0x48, 0x8d, 0x50 | reg, 0x01]); // lea rdx, [reg + 1]
+            asm.emit(&[0x48, 0x3b, 0x53, END]); // cmp rdx, [rbx + END]
+            let skip = asm.jump(JBE);
+            asm.emit(&[0x48, 0x89, 0x53, END]); // mov [rbx + END], rdx
+            skip
+        };
+        asm.patch_here(skip);
+    }
+
+    /// Runs an [`Op::MulAdd`], calling back to the interpreter for checked arithmetic or if the target is
+    /// outside of the memory of the cells.
+    fn mul_add(&mut self, index: usize, offset: isize, factor: i64) {
+        let asm = &mut self.asm;
+        let mut slow = vec![];
+        asm.emit(&[0x48, 0x83, 0x7b, CHECKED, 0]); // cmp qword [rbx + CHECKED], 0
+        slow.push(asm.jump(JNE));
+
+        asm.compare_cell_to_zero();
+        let zero = asm.jump(JE);
+
+        match i32::try_from(offset) {
+            Ok(displacement) => {
+                asm.emit(&[0x49, 0x8d, 0x8d]); // lea rcx, [r13 + offset]
+                asm.emit(&displacement.to_le_bytes());
+                asm.emit(&[0x4c, 0x39, 0xf1]); // cmp rcx, r14
+                slow.push(asm.jump(JAE));
+            }
+            Err(_) => slow.push(asm.jump(JMP)),
+        }
+        self.widen(RCX, offset);
+
+        // Only the lowest bits of the product matter, so the upper bits of rax can be left as they are.
+        let asm = &mut self.asm;
+        asm.load_cell(RAX);
+        asm.multiply(factor);
+        asm.cell(0x00, 0x01, RAX, RCX); // add [r12 + rcx * width], rax
+        let done = asm.jump(JMP);
+
+        for jump in slow {
+            asm.patch_here(jump);
+        }
+        self.callback(index, MUL_ADD, offset as i64, factor);
+        self.asm.patch_here(zero);
+        self.asm.patch_here(done);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, mem::offset_of};
+
+    use crate::{
+        bf_parse, ir::Program, test_programs, ArithmeticMode, CellValue, CellWidth, Cells, Config,
+        TapeMode,
+    };
+
+    use super::{
+        Context, Jit, CALLBACKS, CHECKED, END, INDEX, LENGTH, OP, START, STEPS, STEP_LIMIT, TAPE,
+    };
+
+    type TestContext<'a> = Context<'a, u8, Vec<u8>, Cursor<Vec<u8>>>;
+
+    /// Runs `program` through both [`Program::run`] and [`Jit::run`], checking that the output, result, and
+    /// state of the cells match. Returns the output and the error message, if any.
+    fn compare<T: CellValue>(
+        program: &str,
+        config: &Config,
+        input: &[u8],
+    ) -> (Vec<u8>, Option<String>) {
+        let program = Program::new(&bf_parse(program).expect("parsing returned an error"));
+        let cells: Cells<T> = config.cells();
+
+        let mut expected_cells = cells.clone();
+        let mut expected = vec![];
+        let expected_result = program.run(
+            &mut expected_cells,
+            &mut expected,
+            &mut Cursor::new(input.to_vec()),
+        );
+
+        let mut jit_cells = cells;
+        let mut out = vec![];
+        let result = Jit::new(&program).expect("compiling should succeed").run(
+            &mut jit_cells,
+            &mut out,
+            &mut Cursor::new(input.to_vec()),
+        );
+
+        let result = result.err().map(|err| err.to_string());
+        assert_eq!(out, expected, "outputs should match");
+        assert_eq!(
+            result,
+            expected_result.err().map(|err| err.to_string()),
+            "errors should match"
+        );
+        assert_eq!(
+            jit_cells.cells(),
+            expected_cells.cells(),
+            "tapes should match"
+        );
+        assert_eq!(jit_cells.index(), expected_cells.index());
+        assert_eq!(jit_cells.steps(), expected_cells.steps());
+
+        (out, result)
+    }
+
+    fn compare_config(program: &str, config: &Config, input: &[u8]) -> (Vec<u8>, Option<String>) {
+        match config.cell_width {
+            CellWidth::I8 => compare::<i8>(program, config, input),
+            CellWidth::U8 => compare::<u8>(program, config, input),
+            CellWidth::U16 => compare::<u16>(program, config, input),
+            CellWidth::U32 => compare::<u32>(program, config, input),
+            CellWidth::I64 => compare::<i64>(program, config, input),
+        }
+    }
+
+    #[test]
+    fn context_layout() {
+        assert_eq!(offset_of!(TestContext, tape), TAPE as usize);
+        assert_eq!(offset_of!(TestContext, length), LENGTH as usize);
+        assert_eq!(offset_of!(TestContext, index), INDEX as usize);
+        assert_eq!(offset_of!(TestContext, start), START as usize);
+        assert_eq!(offset_of!(TestContext, end), END as usize);
+        assert_eq!(offset_of!(TestContext, steps), STEPS as usize);
+        assert_eq!(offset_of!(TestContext, step_limit), STEP_LIMIT as usize);
+        assert_eq!(offset_of!(TestContext, checked), CHECKED as usize);
+        assert_eq!(offset_of!(TestContext, op), OP as usize);
+        assert_eq!(offset_of!(TestContext, callbacks), CALLBACKS as usize);
+    }
+
+    #[test]
+    fn esolang_programs() {
+        for (name, program, interpreter, input) in test_programs::programs() {
+            let (_, err) = compare_config(program, interpreter.config(), input);
+            assert!(err.is_none(), "{name} should succeed: {err:?}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for (name, program, interpreter) in test_programs::errors() {
+            let (_, err) = compare_config(program, interpreter.config(), b"");
+            assert!(err.is_some(), "{name} should fail");
+        }
+    }
+
+    #[test]
+    fn checked() {
+        let config = Config {
+            arithmetic_mode: ArithmeticMode::Checked,
+            ..Config::default()
+        };
+
+        let (out, _) = compare_config(test_programs::HELLO_WORLD, &config, b"");
+        assert_eq!(out, b"Hello World!\n");
+        for program in ["-", "+[+]", "+[->-<]", "-[->+<]", "+++[->-<]"] {
+            let (_, err) = compare_config(program, &config, b"");
+            assert!(err.is_some(), "{program} should overflow");
+        }
+    }
+
+    /// The step limit should stop a program at the same op as the interpreter, and the run should resume from
+    /// the same number of steps.
+    #[test]
+    fn step_limit() {
+        for step_limit in [0, 1, 5, 37, 100, 1000] {
+            let config = Config {
+                step_limit: Some(step_limit),
+                ..Config::default()
+            };
+            compare_config(test_programs::HELLO_WORLD, &config, b"");
+            compare_config("+[>>>+[<]>]", &config, b"");
+        }
+    }
+
+    /// Ops that go past the ends of the tape should fall back to the interpreter, and carry on from there.
+    #[test]
+    fn tape_edges() {
+        let growable = Config::default();
+        let bounded = Config {
+            tape_mode: TapeMode::Bounded(4),
+            ..Config::default()
+        };
+        let circular = Config {
+            tape_mode: TapeMode::Circular(4),
+            ..Config::default()
+        };
+
+        for config in [growable, bounded, circular] {
+            for program in [
+                "+++[-<<+>>]<<.",
+                "+[<]+.",
+                ">>+>+[>]<.",
+                "+++[->>>>>++<<<<<]>>>>>.",
+                "+>>+>+<<<[>]>.",
+            ] {
+                compare_config(program, &config, b"");
+            }
+        }
+    }
+
+    /// A tape that grows far in either direction should end up the same as with the interpreter, whether the
+    /// pointer moves within the memory of the cells or past it.
+    #[test]
+    fn growing_tape() {
+        let config = Config {
+            step_limit: Some(10_000),
+            ..Config::default()
+        };
+
+        for program in [
+            "<+".repeat(1000),
+            ">+".repeat(1000),
+            "-[<-]".to_string(),
+            "-[>>>-]".to_string(),
+            "+++[-<<<<<<+>>>>>>]".to_string(),
+            "+++[->>>>>>+<<<<<<]".to_string(),
+            "-[<<<<<<+++[->>>>>>>+<<<<<<<]<-]".to_string(),
+        ] {
+            compare_config(&program, &config, b"");
+        }
+    }
+}
@@ -1,19 +1,67 @@
 pub mod parser;
 
 pub mod commands;
-pub use commands::Command;
+pub use commands::{Command, Commands};
 
 pub mod cells;
-pub use cells::Cells;
+pub use cells::{ArithmeticMode, Cells, EofBehaviour, OutputMode, TapeMode};
+
+pub mod cell_value;
+pub use cell_value::CellValue;
+
+pub mod interpreter;
+pub use interpreter::{CellWidth, Config, Interpreter};
+
+pub mod ir;
+
+pub mod codegen;
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub mod jit;
+
+pub mod vm;
+pub use vm::Vm;
+
+pub mod backend;
+pub use backend::{Backend, Stats};
+
+pub mod source_map;
+pub use source_map::{SourceMap, Span};
 
 pub mod error;
-pub use error::BraincluckError;
+pub use error::{BraincluckError, ParseError, RuntimeError};
 
 mod lexer;
 
+mod x86;
+
+#[cfg(test)]
+mod test_programs;
+
 /// Parses an input containing Brainfuck code.
-pub fn bf_parse(input: &str) -> Result<Vec<Command>, BraincluckError> {
-    parser::bf::BraincluckParser::new()
-        .parse(lexer::Lexer::new(input))
-        .map_err(|err| BraincluckError::ParseError(format!("{:?}", err)))
+pub fn bf_parse(input: &str) -> Result<Commands, BraincluckError> {
+    let (commands, errors) = bf_parse_recovering(input);
+    match errors.into_iter().next() {
+        Some(err) => Err(err.into()),
+        None => Ok(commands),
+    }
+}
+
+/// Parses an input containing Brainfuck code, recovering from unmatched brackets rather than stopping at the
+/// first one. Unexpected `]`s are skipped, and unmatched `[`s are closed at the end of the input.
+///
+/// This returns the commands parsed on a best-effort basis, along with every problem found.
+pub fn bf_parse_recovering(input: &str) -> (Commands, Vec<ParseError>) {
+    let (tokens, errors) = parser::balance_brackets(input);
+    let commands = parser::bf::BraincluckParser::new()
+        .parse(tokens)
+        .expect("the grammar should accept anything with balanced brackets");
+
+    (commands.into(), errors)
+}
+
+/// Parses an input containing Brainfuck code, also returning a [`SourceMap`] with the location of every
+/// command.
+pub fn bf_parse_with_source_map(input: &str) -> Result<(Commands, SourceMap), BraincluckError> {
+    Ok((bf_parse(input)?, SourceMap::new(input)))
 }
@@ -1,10 +1,51 @@
 use lalrpop_util::lalrpop_mod;
-lalrpop_mod!(pub bf);
+lalrpop_mod!(
+    #[allow(clippy::all)]
+    pub bf
+);
+
+use crate::{
+    lexer::{Lexer, Tok},
+    ParseError, SourceMap,
+};
+
+/// Balances the brackets in `input`, returning its tokens with every unexpected `]` removed and a `]` added at
+/// the end for every unmatched `[`. An error for each of these is also returned, in the order they appear.
+pub(crate) fn balance_brackets(input: &str) -> (Vec<(usize, Tok, usize)>, Vec<ParseError>) {
+    let source_map = SourceMap::new(input);
+    let mut tokens = vec![];
+    let mut errors = vec![];
+    let mut open = vec![];
+
+    for (index, (start, tok, end)) in Lexer::new(input).flatten().enumerate() {
+        let span = source_map.spans()[index];
+        match tok {
+            Tok::LeftBracket => open.push(span),
+            Tok::RightBracket if open.pop().is_none() => {
+                errors.push(ParseError::UnexpectedClose { span });
+                continue;
+            }
+            _ => {}
+        }
+        tokens.push((start, tok, end));
+    }
+
+    for _ in &open {
+        tokens.push((input.len(), Tok::RightBracket, input.len()));
+    }
+    errors.extend(
+        open.into_iter()
+            .map(|span| ParseError::UnmatchedOpen { span }),
+    );
+    errors.sort_by_key(|err| err.span().start);
+
+    (tokens, errors)
+}
 
 /// All these tests are based on code from the [Esolang wiki page](https://esolangs.org/wiki/Brainfuck) on the language.
 #[cfg(test)]
 mod tests {
-    use crate::{bf_parse, Command};
+    use crate::{bf_parse, bf_parse_recovering, BraincluckError, Command, ParseError, Span};
 
     #[test]
     fn simple() {
@@ -19,7 +60,7 @@ mod tests {
                 Command::Input,
                 Command::Jump(vec![Command::Right, Command::Left,]),
             ],
-            bf_parse(input).expect("simple parsing returned an error"),
+            *bf_parse(input).expect("simple parsing returned an error"),
             "simple parsing failed"
         );
     }
@@ -39,4 +80,132 @@ mod tests {
 
         bf_parse(input).expect("newline parsing returned an error");
     }
+
+    /// Parsing (and dropping) deeply nested loops should not overflow the stack.
+    #[test]
+    fn deeply_nested() {
+        let input = format!("+{}-{}", "[".repeat(100_000), "]".repeat(100_000));
+
+        let commands = bf_parse(&input).expect("deeply nested parsing returned an error");
+
+        assert_eq!(commands.len(), 2);
+
+        let input = format!("{}]", input);
+        bf_parse(&input).expect_err("unexpected close should fail");
+    }
+
+    #[test]
+    fn unmatched_open() {
+        let input = "+[>\n[-]";
+
+        let err = bf_parse(input).expect_err("unmatched open should fail");
+
+        assert!(matches!(
+            err,
+            BraincluckError::ParseError(ParseError::UnmatchedOpen {
+                span: Span {
+                    start: 1,
+                    end: 2,
+                    line: 1,
+                    column: 2
+                }
+            })
+        ));
+    }
+
+    #[test]
+    fn unexpected_close() {
+        let input = "+[>]\n  -]]";
+
+        let err = bf_parse(input).expect_err("unexpected close should fail");
+
+        assert!(matches!(
+            err,
+            BraincluckError::ParseError(ParseError::UnexpectedClose {
+                span: Span {
+                    start: 8,
+                    end: 9,
+                    line: 2,
+                    column: 4
+                }
+            })
+        ));
+    }
+
+    #[test]
+    fn render() {
+        let input = "++\n+-.] comment\n";
+
+        let err = match bf_parse(input) {
+            Err(BraincluckError::ParseError(err)) => err,
+            _ => panic!("unexpected close should fail"),
+        };
+
+        assert_eq!(
+            err.render(input),
+            "error: unexpected `]` at line 2, column 4\n  |\n2 | +-.] comment\n  |    ^ this `]` has no matching `[`"
+        );
+    }
+
+    /// Recovering should report every bracket problem, rather than just the first.
+    #[test]
+    fn recovering() {
+        let input = "]+[-]]>\n[<]].[";
+
+        let (commands, errors) = bf_parse_recovering(input);
+
+        assert_eq!(
+            *commands,
+            vec![
+                Command::Increment,
+                Command::Jump(vec![Command::Decrement]),
+                Command::Right,
+                Command::Jump(vec![Command::Left]),
+                Command::Output,
+                Command::Jump(vec![]),
+            ]
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| (err.span().line, err.span().column))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (1, 6), (2, 4), (2, 6)]
+        );
+        assert!(matches!(errors[0], ParseError::UnexpectedClose { .. }));
+        assert!(matches!(errors[3], ParseError::UnmatchedOpen { .. }));
+    }
+
+    /// Recovering should automatically close unmatched loops at the end of the input.
+    #[test]
+    fn recovering_unmatched_open() {
+        let (commands, errors) = bf_parse_recovering("+[>[-");
+
+        assert_eq!(
+            *commands,
+            vec![
+                Command::Increment,
+                Command::Jump(vec![
+                    Command::Right,
+                    Command::Jump(vec![Command::Decrement])
+                ]),
+            ]
+        );
+        assert_eq!(errors.len(), 2);
+    }
+
+    /// Recovering should return the same commands as usual for valid input.
+    #[test]
+    fn recovering_valid() {
+        let input = ">++++++++[-<+++++++++>]<.>>+>-[+]++>++>+++[>[->+++<<+++>]<<]>-----.>->
+        +++..+++.>-.<<+[>[+>+]>>]<--------------.>>.+++.------.--------.>+.>+.";
+
+        let (commands, errors) = bf_parse_recovering(input);
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            commands,
+            bf_parse(input).expect("hello world parsing returned an error")
+        );
+    }
 }
@@ -0,0 +1,157 @@
+use crate::lexer::Lexer;
+
+/// The location of a single command character in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The byte offset of the start of the command.
+    pub start: usize,
+    /// The byte offset just after the end of the command.
+    pub end: usize,
+    /// The line of the command, starting from 1.
+    pub line: usize,
+    /// The column of the command, in characters, starting from 1.
+    pub column: usize,
+}
+
+/// A side table with the [`Span`] of every command character in a program, in source order.
+///
+/// Comments are skipped, so the index of a span is the index of the command among all the command
+/// characters, counting both `[` and `]`. This is the same as the index of the matching
+/// [`Instruction`](crate::vm::Instruction), so it can be used with [`Vm::pc`](crate::Vm::pc) directly.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SourceMap {
+    spans: Vec<Span>,
+}
+
+impl SourceMap {
+    /// Creates a [`SourceMap`] for some Brainfuck code.
+    pub fn new(input: &str) -> Self {
+        let mut spans = vec![];
+        let mut line = 1;
+        let mut column = 1;
+        let mut scanned = 0;
+
+        for (start, _, end) in Lexer::new(input).flatten() {
+            for c in input[scanned..start].chars() {
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+            }
+            scanned = start;
+
+            spans.push(Span {
+                start,
+                end,
+                line,
+                column,
+            });
+        }
+
+        Self { spans }
+    }
+
+    /// Returns the span of the command at `index`.
+    pub fn get(&self, index: usize) -> Option<Span> {
+        self.spans.get(index).copied()
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        bf_parse_with_source_map,
+        vm::{Instruction, Status},
+        Cells, Vm,
+    };
+
+    use super::Span;
+
+    #[test]
+    fn spans() {
+        let input = "+ add one\n[-]\n  é.";
+        let (_, source_map) = bf_parse_with_source_map(input).expect("parsing returned an error");
+
+        assert_eq!(
+            source_map.spans(),
+            &[
+                Span {
+                    start: 0,
+                    end: 1,
+                    line: 1,
+                    column: 1
+                },
+                Span {
+                    start: 10,
+                    end: 11,
+                    line: 2,
+                    column: 1
+                },
+                Span {
+                    start: 11,
+                    end: 12,
+                    line: 2,
+                    column: 2
+                },
+                Span {
+                    start: 12,
+                    end: 13,
+                    line: 2,
+                    column: 3
+                },
+                Span {
+                    start: 18,
+                    end: 19,
+                    line: 3,
+                    column: 4
+                },
+            ]
+        );
+    }
+
+    /// The program counter of a [`Vm`] should map to the command it is about to run.
+    #[test]
+    fn vm_pc() {
+        let input = "++\n[>+<-]\n>.";
+        let (commands, source_map) =
+            bf_parse_with_source_map(input).expect("parsing returned an error");
+        let mut vm: Vm = Vm::new(&commands, Cells::default());
+
+        assert_eq!(source_map.len(), vm.instructions().len());
+
+        let mut out = vec![];
+        let mut cursor = Cursor::new(vec![]);
+        while vm.step(&mut out, &mut cursor).expect("step should succeed") != Status::Halted {
+            let span = source_map.get(vm.pc()).expect("pc should have a span");
+            assert_eq!(
+                input[span.start..span.end].chars().next(),
+                Some(match vm.instructions()[vm.pc()] {
+                    Instruction::Right => '>',
+                    Instruction::Left => '<',
+                    Instruction::Increment => '+',
+                    Instruction::Decrement => '-',
+                    Instruction::Output => '.',
+                    Instruction::Input => ',',
+                    Instruction::LoopStart(_) => '[',
+                    Instruction::LoopEnd => ']',
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Programs from the [Esolang wiki page](https://esolangs.org/wiki/Brainfuck) on the language, shared between the
+//! tests of the different ways of running them.
+
+use crate::{ArithmeticMode, CellWidth, EofBehaviour, Interpreter, OutputMode, TapeMode};
+
+/// Straightforward hello world.
+pub const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// Hello world that fails if cell values cannot be set below zero.
+pub const TRICKY_HELLO_WORLD: &str =
+    ">++++++++[-<+++++++++>]<.>>+>-[+]++>++>+++[>[->+++<<+++>]<<]>-----.>->
++++..+++.>-.<<+[>[+>+]>>]<--------------.>>.+++.------.--------.>+.>+.";
+
+/// Hello world that requires wrapping cells.
+pub const WRAPPING_HELLO_WORLD: &str =
+    "--<-<<+[+[<+>--->->->-<<<]>]<<--.<++++++.<<-..<<.<+.>>.>>.<<<.+++.>>.>>-.<<<+.";
+
+/// The shortest code golf hello world. Requires wrapping cells.
+pub const SHORT_HELLO_WORLD: &str =
+    "+[-->-[>>+>-----<<]<--<---]>-.>>>+.>>..+++[.>]<<<<.+++.------.<<-.>>>>+.";
+
+/// Prints the cell width of the interpreter.
+pub const CELL_SIZE: &str = r##"
+    Calculate the value 256 and test if it's zero
+    If the interpreter errors on overflow this is where it'll happen
+    ++++++++[>++++++++<-]>[<++++>-]
+    +<[>-<
+        Not zero so multiply by 256 again to get 65536
+        [>++++<-]>[<++++++++>-]<[>++++++++<-]
+        +>[>
+            # Print "32"
+            ++++++++++[>+++++<-]>+.-.[-]<
+        <[-]<->] <[>>
+            # Print "16"
+            +++++++[>+++++++<-]>.+++++.[-]<
+    <<-]] >[>
+        # Print "8"
+        ++++++++[>+++++++<-]>.[-]<
+    <-]<
+    # Print " bit cells\n"
+    +++++++++++[>+++>+++++++++>+++++++++>+<<<<-]>-.>-.+++++++.+++++++++++.<.
+    >>.++.+++++++..<-.>>-
+    Clean up used cells.
+    [[-]<]
+    "##;
+
+/// A cat program where EOF returns 0.
+pub const CAT: &str = ",[.,]";
+
+/// A cat program where EOF returns -1.
+pub const CAT_NEGATIVE_ONE: &str = ",+[-.,+]";
+
+/// Programs that should run successfully, with their names, settings, and input.
+pub fn programs() -> Vec<(&'static str, &'static str, Interpreter, &'static [u8])> {
+    let interpreter = Interpreter::default();
+    let zero = Interpreter::default().with_eof_behaviour(EofBehaviour::Zero);
+    let negative_one = Interpreter::default().with_eof_behaviour(EofBehaviour::NegativeOne);
+    let unchanged = Interpreter::default().with_eof_behaviour(EofBehaviour::Unchanged);
+    vec![
+        ("hello_world", HELLO_WORLD, interpreter, b""),
+        ("tricky_hello_world", TRICKY_HELLO_WORLD, interpreter, b""),
+        (
+            "wrapping_hello_world",
+            WRAPPING_HELLO_WORLD,
+            interpreter,
+            b"",
+        ),
+        ("short_hello_world", SHORT_HELLO_WORLD, interpreter, b""),
+        (
+            "bounded_hello_world",
+            HELLO_WORLD,
+            interpreter.with_tape_mode(TapeMode::Bounded(30000)),
+            b"",
+        ),
+        (
+            "circular_hello_world",
+            TRICKY_HELLO_WORLD,
+            interpreter.with_tape_mode(TapeMode::Circular(10)),
+            b"",
+        ),
+        (
+            "cell_size_i8",
+            CELL_SIZE,
+            interpreter.with_cell_width(CellWidth::I8),
+            b"",
+        ),
+        ("cell_size_u8", CELL_SIZE, interpreter, b""),
+        (
+            "cell_size_u16",
+            CELL_SIZE,
+            interpreter.with_cell_width(CellWidth::U16),
+            b"",
+        ),
+        (
+            "cell_size_u32",
+            CELL_SIZE,
+            interpreter.with_cell_width(CellWidth::U32),
+            b"",
+        ),
+        (
+            "cell_size_i64",
+            CELL_SIZE,
+            interpreter.with_cell_width(CellWidth::I64),
+            b"",
+        ),
+        ("cat", CAT, interpreter, b"Hello!\0"),
+        ("cat_zero", CAT, zero, b"Hello!"),
+        (
+            "cat_negative_one",
+            CAT_NEGATIVE_ONE,
+            negative_one,
+            b"Hello!",
+        ),
+        ("cat_unchanged", ",[.[-],]", unchanged, b"Hello!"),
+        (
+            "text",
+            "++++++++[>++++++++<-]>[>++>+++<<-]>+.>.",
+            interpreter.with_output_mode(OutputMode::Text),
+            b"",
+        ),
+        ("multiply", "+++[->++++++<]-[->+<]>.", interpreter, b""),
+        (
+            "multiply_wide",
+            "-[->+++<]>.",
+            interpreter.with_cell_width(CellWidth::U16),
+            b"",
+        ),
+        (
+            "grow_left",
+            "<<<+++++[>>>++++++++++<<<-]>>>+++++.",
+            interpreter,
+            b"",
+        ),
+    ]
+}
+
+/// Programs that should stop with an error, with their names and settings. They get no input.
+pub fn errors() -> Vec<(&'static str, &'static str, Interpreter)> {
+    vec![
+        ("eof", "+.,", Interpreter::default()),
+        (
+            "bounded",
+            "+.>>+>",
+            Interpreter::default().with_tape_mode(TapeMode::Bounded(3)),
+        ),
+        (
+            "bounded_left",
+            "<",
+            Interpreter::default().with_tape_mode(TapeMode::Bounded(3)),
+        ),
+        (
+            "bounded_there_and_back",
+            "<>",
+            Interpreter::default().with_tape_mode(TapeMode::Bounded(2)),
+        ),
+        (
+            "bounded_run",
+            ">>>",
+            Interpreter::default().with_tape_mode(TapeMode::Bounded(2)),
+        ),
+        (
+            "checked",
+            "-+",
+            Interpreter::default().with_arithmetic_mode(ArithmeticMode::Checked),
+        ),
+        (
+            "step_limit",
+            ">+[.]",
+            Interpreter::default().with_step_limit(100),
+        ),
+        (
+            "text",
+            "-.",
+            Interpreter::default()
+                .with_output_mode(OutputMode::Text)
+                .with_cell_width(CellWidth::U16),
+        ),
+    ]
+}
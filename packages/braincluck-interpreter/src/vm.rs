@@ -0,0 +1,441 @@
+use std::io::{self, Read, Write};
+
+use crate::{BraincluckError, CellValue, Cells, Command};
+
+/// A single instruction run by the [`Vm`]. Each instruction corresponds to one Brainfuck command character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Right,
+    Left,
+    Increment,
+    Decrement,
+    Output,
+    Input,
+    /// The start of a loop (`[`), holding the index of its matching [`Instruction::LoopEnd`].
+    LoopStart(usize),
+    /// The end of a loop (`]`).
+    LoopEnd,
+}
+
+impl Instruction {
+    /// Flattens a list of commands into a list of instructions.
+    pub fn flatten(commands: &[Command]) -> Vec<Instruction> {
+        let mut instructions = vec![];
+        let mut blocks = vec![commands.iter()];
+        let mut loop_starts = vec![];
+
+        // This walks the tree with an explicit stack so deeply nested loops can't overflow the native one.
+        while let Some(block) = blocks.last_mut() {
+            match block.next() {
+                Some(Command::Right) => instructions.push(Instruction::Right),
+                Some(Command::Left) => instructions.push(Instruction::Left),
+                Some(Command::Increment) => instructions.push(Instruction::Increment),
+                Some(Command::Decrement) => instructions.push(Instruction::Decrement),
+                Some(Command::Output) => instructions.push(Instruction::Output),
+                Some(Command::Input) => instructions.push(Instruction::Input),
+                Some(Command::Jump(inner)) => {
+                    loop_starts.push(instructions.len());
+                    instructions.push(Instruction::LoopStart(0));
+                    blocks.push(inner.iter());
+                }
+                None => {
+                    blocks.pop();
+                    if let Some(start) = loop_starts.pop() {
+                        instructions[start] = Instruction::LoopStart(instructions.len());
+                        instructions.push(Instruction::LoopEnd);
+                    }
+                }
+            }
+        }
+
+        instructions
+    }
+}
+
+/// The state of a [`Vm`] after running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// There are still instructions left to run.
+    Running,
+    /// The next instruction reads input.
+    AwaitingInput,
+    /// There are no instructions left to run.
+    Halted,
+}
+
+/// A virtual machine that runs Brainfuck programs one instruction at a time, which allows a run to be paused
+/// and resumed at any point.
+///
+/// Unlike [`Cells::interpret`], the program is flattened into a list of [`Instruction`]s, and the program
+/// counter and the stack of currently running loops are tracked explicitly. [`Cells`] is still used as the
+/// tape, so all of its settings (such as the step limit) apply.
+///
+/// If an instruction returns an error, the program counter is not advanced, so the run can be resumed after
+/// dealing with it (for example, by raising the step limit).
+#[derive(Debug, Clone)]
+pub struct Vm<T: CellValue = u8> {
+    instructions: Vec<Instruction>,
+    pc: usize,
+    loop_stack: Vec<usize>,
+    cells: Cells<T>,
+}
+
+impl<T: CellValue> Vm<T> {
+    /// Creates a new [`Vm`] that will run `commands` on `cells`.
+    pub fn new(commands: &[Command], cells: Cells<T>) -> Self {
+        Self {
+            instructions: Instruction::flatten(commands),
+            pc: 0,
+            loop_stack: vec![],
+            cells,
+        }
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the index of the next instruction to run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the indices of the [`Instruction::LoopStart`]s of the loops that are currently running,
+    /// from outermost to innermost.
+    pub fn loop_stack(&self) -> &[usize] {
+        &self.loop_stack
+    }
+
+    pub fn cells(&self) -> &Cells<T> {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut Cells<T> {
+        &mut self.cells
+    }
+
+    pub fn into_cells(self) -> Cells<T> {
+        self.cells
+    }
+
+    pub fn status(&self) -> Status {
+        match self.instructions.get(self.pc) {
+            None => Status::Halted,
+            Some(Instruction::Input) => Status::AwaitingInput,
+            Some(_) => Status::Running,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.instructions.len()
+    }
+
+    /// Runs a single instruction. Does nothing if the program has halted.
+    ///
+    /// If the instruction fails, the error is returned as a [`BraincluckError::Runtime`] pointing at the
+    /// program counter, and its step is not counted, so it is only counted once when the run is resumed.
+    pub fn step<W: Write, R: Read>(
+        &mut self,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<Status, BraincluckError> {
+        let steps = self.cells.steps();
+        self.run_instruction(output, input).map_err(|err| {
+            let err = self.cells.runtime_error(err, self.pc);
+            self.cells.set_steps(steps);
+            err
+        })
+    }
+
+    fn run_instruction<W: Write, R: Read>(
+        &mut self,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<Status, BraincluckError> {
+        let instruction = match self.instructions.get(self.pc) {
+            Some(instruction) => *instruction,
+            None => return Ok(Status::Halted),
+        };
+
+        self.cells.count_step()?;
+        match instruction {
+            Instruction::Right => self.cells.right()?,
+            Instruction::Left => self.cells.left()?,
+            Instruction::Increment => self.cells.increment()?,
+            Instruction::Decrement => self.cells.decrement()?,
+            Instruction::Output => self.cells.write_output(output)?,
+            Instruction::Input => self.cells.read_input(input)?,
+            Instruction::LoopStart(end) => {
+                if self.cells.is_current_cell_zero() {
+                    self.pc = end + 1;
+                    return Ok(self.status());
+                }
+                self.loop_stack.push(self.pc);
+            }
+            Instruction::LoopEnd => {
+                if let Some(&start) = self.loop_stack.last() {
+                    if !self.cells.is_current_cell_zero() {
+                        self.pc = start + 1;
+                        return Ok(self.status());
+                    }
+                    self.loop_stack.pop();
+                }
+            }
+        }
+        self.pc += 1;
+
+        Ok(self.status())
+    }
+
+    /// Runs at most `steps` instructions, stopping early if the program halts.
+    pub fn run_for<W: Write, R: Read>(
+        &mut self,
+        steps: usize,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<Status, BraincluckError> {
+        for _ in 0..steps {
+            if self.step(output, input)? == Status::Halted {
+                break;
+            }
+        }
+
+        Ok(self.status())
+    }
+
+    /// Runs until the next instruction reads input or the program halts. The input instruction itself is
+    /// not run; call [`Vm::step`] with some input to continue past it.
+    pub fn run_until_input<W: Write>(&mut self, output: &mut W) -> Result<Status, BraincluckError> {
+        loop {
+            match self.status() {
+                Status::Running => {
+                    self.step(output, &mut io::empty())?;
+                }
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs until the program halts.
+    pub fn run<W: Write, R: Read>(
+        &mut self,
+        output: &mut W,
+        input: &mut R,
+    ) -> Result<(), BraincluckError> {
+        while self.step(output, input)? != Status::Halted {}
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        bf_parse, ArithmeticMode, BraincluckError, Cells, Command, EofBehaviour, TapeMode,
+    };
+
+    use super::{Instruction, Status, Vm};
+
+    #[test]
+    fn flatten() {
+        let commands = bf_parse("+[>[-]<-].").expect("parsing returned an error");
+
+        assert_eq!(
+            Instruction::flatten(&commands),
+            vec![
+                Instruction::Increment,
+                Instruction::LoopStart(8),
+                Instruction::Right,
+                Instruction::LoopStart(5),
+                Instruction::Decrement,
+                Instruction::LoopEnd,
+                Instruction::Left,
+                Instruction::Decrement,
+                Instruction::LoopEnd,
+                Instruction::Output,
+            ]
+        );
+    }
+
+    /// Running one instruction at a time should give the same result as [`Cells::interpret`].
+    #[test]
+    fn hello_world() {
+        let program = ">++++++++[-<+++++++++>]<.>>+>-[+]++>++>+++[>[->+++<<+++>]<<]>-----.>->
+        +++..+++.>-.<<+[>[+>+]>>]<--------------.>>.+++.------.--------.>+.>+.";
+        let commands = bf_parse(program).expect("hello world parsing returned an error");
+        let mut vm: Vm = Vm::new(&commands, Cells::default());
+
+        let mut out = vec![];
+        let mut cursor = Cursor::new(vec![]);
+        while vm
+            .run_for(7, &mut out, &mut cursor)
+            .expect("run should succeed")
+            != Status::Halted
+        {}
+
+        let mut cells: Cells = Cells::default();
+        let mut expected = vec![];
+        cells
+            .interpret(&commands, &mut expected, &mut Cursor::new(vec![]))
+            .expect("interpret should succeed");
+
+        assert_eq!(out, expected);
+        assert_eq!(
+            String::from_utf8(out).expect("string should be valid utf8"),
+            "Hello World!\n"
+        );
+        assert_eq!(vm.cells().steps(), cells.steps());
+    }
+
+    /// A run can be paused inside of a loop.
+    #[test]
+    fn pause_in_loop() {
+        let commands = bf_parse("+++[>++<-]").expect("parsing returned an error");
+        let mut vm: Vm = Vm::new(&commands, Cells::default());
+
+        let mut out = vec![];
+        let mut cursor = Cursor::new(vec![]);
+        vm.run_for(6, &mut out, &mut cursor)
+            .expect("run should succeed");
+
+        assert_eq!(vm.pc(), 6);
+        assert_eq!(vm.loop_stack(), &[3]);
+        assert_eq!(vm.cells().index(), 1);
+        assert_eq!(vm.cells().current(), 1);
+
+        vm.run(&mut out, &mut cursor).expect("run should succeed");
+
+        assert!(vm.is_halted());
+        assert!(vm.loop_stack().is_empty());
+        assert_eq!(vm.cells().cells()[1], 6);
+    }
+
+    /// Running until input should stop before every `,`.
+    #[test]
+    fn run_until_input() {
+        let commands = bf_parse(",[.,]").expect("cat parsing returned an error");
+        let mut vm: Vm = Vm::new(
+            &commands,
+            Cells::default().with_eof_behaviour(EofBehaviour::Zero),
+        );
+
+        let mut out = vec![];
+        for byte in b"Hi" {
+            assert_eq!(
+                vm.run_until_input(&mut out).expect("run should succeed"),
+                Status::AwaitingInput
+            );
+            vm.step(&mut out, &mut Cursor::new(vec![*byte]))
+                .expect("step should succeed");
+        }
+
+        assert_eq!(
+            vm.run_until_input(&mut out).expect("run should succeed"),
+            Status::AwaitingInput
+        );
+        assert_eq!(
+            vm.step(&mut out, &mut Cursor::new(vec![]))
+                .expect("step should succeed"),
+            Status::Running
+        );
+        assert_eq!(
+            vm.run_until_input(&mut out).expect("run should succeed"),
+            Status::Halted
+        );
+        assert_eq!(out, b"Hi");
+    }
+
+    /// Errors should not advance the program, so it can be resumed.
+    #[test]
+    fn resume_after_step_limit() {
+        let commands = bf_parse("++[>+++<-]>.").expect("parsing returned an error");
+        let mut vm: Vm = Vm::new(&commands, Cells::default().with_step_limit(5));
+
+        let mut out = vec![];
+        let mut cursor = Cursor::new(vec![]);
+        let result = vm.run(&mut out, &mut cursor);
+        assert!(matches!(
+            result.as_ref().map_err(BraincluckError::inner),
+            Err(BraincluckError::StepLimitExceeded { steps: 5, .. })
+        ));
+        assert_eq!(vm.pc(), 5);
+
+        vm.cells_mut().set_step_limit(None);
+        vm.run(&mut out, &mut cursor).expect("run should succeed");
+
+        assert_eq!(out, vec![6]);
+    }
+
+    /// Resuming after a failed instruction should only count its step once.
+    #[test]
+    fn resume_after_overflow() {
+        let commands = bf_parse("-+").expect("parsing returned an error");
+        let mut vm: Vm = Vm::new(
+            &commands,
+            Cells::default().with_arithmetic_mode(ArithmeticMode::Checked),
+        );
+
+        let mut out = vec![];
+        let mut cursor = Cursor::new(vec![]);
+        match vm.run(&mut out, &mut cursor) {
+            Err(BraincluckError::Runtime(context)) => assert_eq!(context.steps, 1),
+            result => panic!("expected a runtime error, got {result:?}"),
+        }
+        assert_eq!(vm.pc(), 0);
+        assert_eq!(vm.cells().steps(), 0);
+
+        vm.cells_mut().input(1);
+        vm.run(&mut out, &mut cursor).expect("run should succeed");
+
+        assert_eq!(vm.cells().steps(), 2);
+        assert_eq!(vm.cells().current(), 1);
+    }
+
+    /// Errors should point at the same command as they do with [`Cells::interpret`].
+    #[test]
+    fn runtime_error() {
+        let commands = bf_parse("+[>+]").expect("parsing returned an error");
+        let mut vm: Vm = Vm::new(
+            &commands,
+            Cells::default().with_tape_mode(TapeMode::Bounded(3)),
+        );
+
+        match vm.run(&mut vec![], &mut Cursor::new(vec![])) {
+            Err(BraincluckError::Runtime(context)) => {
+                assert_eq!(context.instruction, 2);
+                assert_eq!(context.instruction, vm.pc());
+                assert_eq!(context.index, 2);
+            }
+            result => panic!("expected a runtime error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn empty_program() {
+        let mut vm: Vm = Vm::new(&Vec::<Command>::new(), Cells::default());
+
+        assert_eq!(vm.status(), Status::Halted);
+        assert_eq!(
+            vm.step(&mut vec![], &mut Cursor::new(vec![]))
+                .expect("step should succeed"),
+            Status::Halted
+        );
+        assert_eq!(vm.cells().steps(), 0);
+    }
+
+    /// Deeply nested loops should not overflow the stack.
+    #[test]
+    fn deeply_nested() {
+        let program = format!("+{}-{}.", "[".repeat(100_000), "]".repeat(100_000));
+        let commands = bf_parse(&program).expect("deeply nested parsing returned an error");
+        let mut vm: Vm = Vm::new(&commands, Cells::default());
+
+        let mut out = vec![];
+        vm.run(&mut out, &mut Cursor::new(vec![]))
+            .expect("run should succeed");
+
+        assert_eq!(out, vec![0]);
+    }
+}
@@ -0,0 +1,145 @@
+//! A tiny x86-64 assembler, with only the instructions needed to compile an optimized
+//! [`Program`](crate::ir::Program) to machine code. Cells are always addressed as `[r12 + index * width]`,
+//! with the index of the current cell in r13.
+
+// Registers, as numbered in instruction encodings.
+pub(crate) const RAX: u8 = 0;
+#[cfg_attr(
+    not(all(feature = "jit", target_arch = "x86_64", target_os = "linux")),
+    allow(dead_code)
+)]
+pub(crate) const RCX: u8 = 1;
+pub(crate) const R8: u8 = 8;
+pub(crate) const R13: u8 = 13;
+
+// Jump opcodes, which are all followed by a 32 bit offset.
+pub(crate) const JMP: &[u8] = &[0xe9];
+pub(crate) const CALL: &[u8] = &[0xe8];
+pub(crate) const JE: &[u8] = &[0x0f, 0x84];
+pub(crate) const JNE: &[u8] = &[0x0f, 0x85];
+pub(crate) const JA: &[u8] = &[0x0f, 0x87];
+pub(crate) const JAE: &[u8] = &[0x0f, 0x83];
+pub(crate) const JB: &[u8] = &[0x0f, 0x82];
+#[cfg_attr(
+    not(all(feature = "jit", target_arch = "x86_64", target_os = "linux")),
+    allow(dead_code)
+)]
+pub(crate) const JBE: &[u8] = &[0x0f, 0x86];
+pub(crate) const JGE: &[u8] = &[0x0f, 0x8d];
+pub(crate) const JS: &[u8] = &[0x0f, 0x88];
+pub(crate) const JNS: &[u8] = &[0x0f, 0x89];
+
+/// Emits x86-64 machine code for cells of a given width.
+pub(crate) struct Assembler {
+    pub(crate) code: Vec<u8>,
+    /// The size of a cell in bytes.
+    width: u32,
+}
+
+impl Assembler {
+    pub(crate) fn new(width: u32) -> Self {
+        Self {
+            code: vec![],
+            width,
+        }
+    }
+
+    pub(crate) fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn here(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Emits a jump or call with a placeholder target, returning where to [`patch`](Self::patch) it.
+    pub(crate) fn jump(&mut self, opcode: &[u8]) -> usize {
+        self.emit(opcode);
+        self.emit(&[0; 4]);
+        self.here() - 4
+    }
+
+    pub(crate) fn jump_to(&mut self, opcode: &[u8], target: usize) {
+        let jump = self.jump(opcode);
+        self.patch(jump, target);
+    }
+
+    /// Points the jump (or any other 32 bit offset from the next instruction) at `jump` to `target`.
+    pub(crate) fn patch(&mut self, jump: usize, target: usize) {
+        let offset = target as i64 - (jump as i64 + 4);
+        self.code[jump..jump + 4].copy_from_slice(&(offset as i32).to_le_bytes());
+    }
+
+    pub(crate) fn patch_here(&mut self, jump: usize) {
+        self.patch(jump, self.here());
+    }
+
+    /// Emits an instruction on the cell at `[r12 + index * width]`, using `byte_opcode` for 8 bit cells and
+    /// `opcode` otherwise. `reg` is either a register or an extension of the opcode.
+    pub(crate) fn cell(&mut self, byte_opcode: u8, opcode: u8, reg: u8, index: u8) {
+        if self.width == 2 {
+            self.emit(&[0x66]);
+        }
+        let rex = 0x41
+            | if self.width == 8 { 0x08 } else { 0 }
+            | if reg >= 8 { 0x04 } else { 0 }
+            | if index >= 8 { 0x02 } else { 0 };
+        let opcode = if self.width == 1 { byte_opcode } else { opcode };
+        let modrm = (reg & 7) << 3 | 0b100;
+        let sib = (self.width.trailing_zeros() as u8) << 6 | (index & 7) << 3 | 0b100;
+        self.emit(&[rex, opcode, modrm, sib]);
+    }
+
+    /// Emits an immediate the size of a cell, or 32 bits for 64 bit cells.
+    pub(crate) fn immediate(&mut self, value: i64) {
+        let bytes = value.to_le_bytes();
+        self.emit(&bytes[..self.width.min(4) as usize]);
+    }
+
+    pub(crate) fn compare_cell_to_zero(&mut self) {
+        self.cell(0x80, 0x83, 7, R13); // cmp [cell], 0
+        self.emit(&[0]);
+    }
+
+    /// Adds `delta` to the current cell, wrapping around.
+    pub(crate) fn add_cell(&mut self, delta: i64) {
+        if self.width == 8 && i32::try_from(delta).is_err() {
+            self.emit(&[0x48, 0xb8]); // mov rax, delta
+            self.emit(&delta.to_le_bytes());
+            self.cell(0x00, 0x01, RAX, R13); // add [cell], rax
+        } else {
+            self.cell(0x80, 0x81, 0, R13); // add [cell], delta
+            self.immediate(delta);
+        }
+    }
+
+    pub(crate) fn clear_cell(&mut self) {
+        self.cell(0xc6, 0xc7, 0, R13); // mov [cell], 0
+        self.immediate(0);
+    }
+
+    /// Loads the current cell into the lowest bits of `reg`, leaving the upper bits as they were.
+    pub(crate) fn load_cell(&mut self, reg: u8) {
+        self.cell(0x8a, 0x8b, reg, R13); // mov reg, [cell]
+    }
+
+    /// Stores the lowest bits of `reg` into the current cell.
+    pub(crate) fn store_cell(&mut self, reg: u8) {
+        self.cell(0x88, 0x89, reg, R13); // mov [cell], reg
+    }
+
+    /// Multiplies rax by `factor`, clobbering rdx.
+    pub(crate) fn multiply(&mut self, factor: i64) {
+        match i32::try_from(factor) {
+            Ok(factor) => {
+                self.emit(&[0x48, 0x69, 0xc0]); // imul rax, rax, factor
+                self.emit(&factor.to_le_bytes());
+            }
+            Err(_) => {
+                self.emit(&[0x48, 0xba]); // mov rdx, factor
+                self.emit(&factor.to_le_bytes());
+                self.emit(&[0x48, 0x0f, 0xaf, 0xc2]); // imul rax, rdx
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! The [`bf!`] macro, which compiles a Brainfuck program into Rust code at build time.
+
+use braincluck_interpreter::{bf_parse_recovering, Command, ParseError};
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+/// Compiles a Brainfuck program into a Rust function, so the program is parsed while building rather than at
+/// runtime. Unmatched brackets are reported as compile errors.
+///
+/// The function has the signature:
+///
+/// ```text
+/// fn<T: CellValue, W: Write, R: Read>(
+///     cells: &mut Cells<T>,
+///     output: &mut W,
+///     input: &mut R,
+/// ) -> Result<(), BraincluckError>
+/// ```
+///
+/// and runs the program on `cells`, following its settings. Compiled code doesn't count steps, so if `cells` has
+/// a step limit, this returns a [`BraincluckError::UnsupportedConfig`] without running anything. Runs of the
+/// same command are folded into a single [`Cells::add`] or [`Cells::move_by`], and errors are returned as-is,
+/// without a [`BraincluckError::Runtime`] around them.
+///
+/// ```
+/// use braincluck_interpreter::Cells;
+/// use braincluck_macros::bf;
+///
+/// let mut out = vec![];
+/// bf!("++++++++[>++++++++<-]>+.")(&mut Cells::<u8>::default(), &mut out, &mut std::io::empty()).unwrap();
+/// assert_eq!(out, b"A");
+/// ```
+///
+/// Programs with unmatched brackets don't build:
+///
+/// ```compile_fail
+/// let program = braincluck_macros::bf!("+[>+<-");
+/// ```
+///
+/// [`Cells::add`]: braincluck_interpreter::Cells::add
+/// [`Cells::move_by`]: braincluck_interpreter::Cells::move_by
+/// [`BraincluckError::Runtime`]: braincluck_interpreter::BraincluckError::Runtime
+/// [`BraincluckError::UnsupportedConfig`]: braincluck_interpreter::BraincluckError::UnsupportedConfig
+#[proc_macro]
+pub fn bf(input: TokenStream) -> TokenStream {
+    let program = parse_macro_input!(input as LitStr);
+    expand(&program)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(program: &LitStr) -> syn::Result<TokenStream2> {
+    let source = program.value();
+    let (commands, errors) = bf_parse_recovering(&source);
+    if let Some(err) = errors
+        .iter()
+        .map(|err| syn::Error::new(error_span(program, &source, err), err))
+        .reduce(|mut combined, err| {
+            combined.combine(err);
+            combined
+        })
+    {
+        return Err(err);
+    }
+
+    let body = block(&commands);
+    Ok(quote! {
+        {
+            #[allow(unused_variables)]
+            fn program<T, W, R>(
+                cells: &mut ::braincluck_interpreter::Cells<T>,
+                output: &mut W,
+                input: &mut R,
+            ) -> ::core::result::Result<(), ::braincluck_interpreter::BraincluckError>
+            where
+                T: ::braincluck_interpreter::CellValue,
+                W: ::std::io::Write,
+                R: ::std::io::Read,
+            {
+                if cells.step_limit().is_some() {
+                    return ::core::result::Result::Err(
+                        ::braincluck_interpreter::BraincluckError::UnsupportedConfig("a step limit"),
+                    );
+                }
+
+                #body
+                ::core::result::Result::Ok(())
+            }
+
+            program
+        }
+    })
+}
+
+/// Points at the offending bracket inside the string literal if possible, or at the whole literal otherwise.
+/// Pointing inside a literal only works on nightly, and only if it has no escapes.
+fn error_span(program: &LitStr, source: &str, err: &ParseError) -> Span {
+    let token = program.token();
+    if token.to_string() != format!("\"{source}\"") {
+        return program.span();
+    }
+
+    // Skip the opening quote.
+    let span = err.span();
+    token
+        .subspan(span.start + 1..span.end + 1)
+        .unwrap_or_else(|| program.span())
+}
+
+/// Generates the statements for a block of commands, folding runs of the same `+`, `-`, `>` or `<`. Runs that go
+/// different ways, like `+-`, are kept apart, so they stop with the same errors as the interpreter.
+fn block(commands: &[Command]) -> TokenStream2 {
+    let mut statements = TokenStream2::new();
+    let mut commands = commands.iter().peekable();
+    while let Some(command) = commands.next() {
+        let mut count = 1;
+        if matches!(
+            command,
+            Command::Increment | Command::Decrement | Command::Right | Command::Left
+        ) {
+            while commands.next_if_eq(&command).is_some() {
+                count += 1;
+            }
+        }
+
+        match command {
+            Command::Increment | Command::Decrement => {
+                let delta = delta(command) * count as i64;
+                statements.extend(quote! { cells.add(#delta)?; });
+            }
+            Command::Right | Command::Left => {
+                let offset = offset(command) * count as isize;
+                statements.extend(quote! { cells.move_by(#offset)?; });
+            }
+            Command::Output => statements.extend(quote! { cells.write_output(output)?; }),
+            Command::Input => statements.extend(quote! { cells.read_input(input)?; }),
+            Command::Jump(inner) => {
+                let inner = block(inner);
+                statements.extend(quote! {
+                    while !cells.is_current_cell_zero() {
+                        #inner
+                    }
+                });
+            }
+        }
+    }
+
+    statements
+}
+
+fn delta(command: &Command) -> i64 {
+    if *command == Command::Increment {
+        1
+    } else {
+        -1
+    }
+}
+
+fn offset(command: &Command) -> isize {
+    if *command == Command::Right {
+        1
+    } else {
+        -1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::LitStr;
+
+    use super::expand;
+
+    fn expand_str(program: &str) -> Result<String, String> {
+        let literal: LitStr = syn::parse_str(&format!("{program:?}")).expect("should be a literal");
+        expand(&literal)
+            .map(|tokens| tokens.to_string())
+            .map_err(|err| {
+                err.into_iter()
+                    .map(|err| err.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+    }
+
+    #[test]
+    fn folding() {
+        let expanded = expand_str("+++-->><<<[-]+-.,").expect("expanding should succeed");
+
+        assert!(expanded.contains("cells . add (3i64)"), "{expanded}");
+        assert!(expanded.contains("cells . add (- 2i64)"), "{expanded}");
+        assert!(expanded.contains("cells . move_by (2isize)"), "{expanded}");
+        assert!(
+            expanded.contains("cells . move_by (- 3isize)"),
+            "{expanded}"
+        );
+        assert!(
+            expanded.contains("while ! cells . is_current_cell_zero ()"),
+            "{expanded}"
+        );
+        assert_eq!(expanded.matches("cells . add").count(), 5, "{expanded}");
+        assert!(
+            expanded.contains("cells . write_output (output)"),
+            "{expanded}"
+        );
+        assert!(
+            expanded.contains("cells . read_input (input)"),
+            "{expanded}"
+        );
+    }
+
+    #[test]
+    fn bracket_errors() {
+        assert_eq!(
+            expand_str("+]\n[[-]").expect_err("expanding should fail"),
+            "unexpected `]` at line 1, column 2\nunmatched `[` at line 2, column 1"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Checks that programs compiled with `bf!` behave the same as the interpreter.
+
+use std::io::Cursor;
+
+use braincluck_interpreter::{
+    ArithmeticMode, BraincluckError, CellValue, Cells, EofBehaviour, Interpreter, OutputMode,
+    TapeMode,
+};
+use braincluck_macros::bf;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// The signature of a compiled program, used with a `Vec` for output and a `Cursor` for input.
+type Program<T> =
+    fn(&mut Cells<T>, &mut Vec<u8>, &mut Cursor<Vec<u8>>) -> Result<(), BraincluckError>;
+
+/// Runs a compiled program on `cells` with `input`, returning what it wrote.
+fn run<T: CellValue>(
+    program: Program<T>,
+    mut cells: Cells<T>,
+    input: &[u8],
+) -> Result<Vec<u8>, BraincluckError> {
+    let mut out = vec![];
+    program(&mut cells, &mut out, &mut Cursor::new(input.to_vec()))?;
+
+    Ok(out)
+}
+
+#[test]
+fn hello_world() {
+    let out = run(
+        bf!("++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++."),
+        Cells::<u8>::default(),
+        b"",
+    )
+    .expect("run should succeed");
+
+    assert_eq!(
+        out,
+        Interpreter::default().run_to_vec(HELLO_WORLD, b"").unwrap()
+    );
+}
+
+#[test]
+fn wrapping_hello_world() {
+    let out = run(
+        bf!("--<-<<+[+[<+>--->->->-<<<]>]<<--.<++++++.<<-..<<.<+.>>.>>.<<<.+++.>>.>>-.<<<+."),
+        Cells::<u8>::default(),
+        b"",
+    )
+    .expect("run should succeed");
+
+    assert_eq!(out, b"Hello, World!");
+}
+
+/// The settings of the cells should be followed.
+#[test]
+fn settings() {
+    let cat = bf!(",[.,]");
+    let out = run(
+        cat,
+        Cells::<u8>::default().with_eof_behaviour(EofBehaviour::Zero),
+        b"Hello!",
+    )
+    .expect("run should succeed");
+    assert_eq!(out, b"Hello!");
+
+    let out = run(
+        bf!("++++++++[>++++++++++++++++++++++++<-]>+."),
+        Cells::<u16>::default().with_output_mode(OutputMode::Text),
+        b"",
+    )
+    .expect("run should succeed");
+    assert_eq!(out, "Á".as_bytes());
+}
+
+/// Programs should stop with the same errors as the interpreter, even where runs are folded.
+#[test]
+fn errors() {
+    let bounded = Interpreter::default().with_tape_mode(TapeMode::Bounded(2));
+    let checked = Interpreter::default().with_arithmetic_mode(ArithmeticMode::Checked);
+    let cases: [(&str, Program<u8>, Interpreter); 6] = [
+        (",", bf!(","), Interpreter::default()),
+        (">>>", bf!(">>>"), bounded),
+        ("<>", bf!("<>"), bounded),
+        ("<<<", bf!("<<<"), bounded),
+        ("-", bf!("-"), checked),
+        ("-+", bf!("-+"), checked),
+    ];
+
+    for (program, compiled, interpreter) in cases {
+        let expected = interpreter
+            .run_to_vec(program, b"")
+            .expect_err("interpret should fail");
+        let err = run(compiled, interpreter.config().cells(), b"").expect_err("run should fail");
+
+        assert_eq!(
+            err.to_string(),
+            expected.inner().to_string(),
+            "{program} error should match"
+        );
+    }
+}
+
+/// Compiled programs don't count steps, so they should refuse to run with a step limit.
+#[test]
+fn step_limit() {
+    assert!(matches!(
+        run(bf!("+"), Cells::<u8>::default().with_step_limit(10), b""),
+        Err(BraincluckError::UnsupportedConfig(_))
+    ));
+}
@@ -1,6 +1,8 @@
-use braincluck_interpreter::{bf_parse, Cells};
-use std::io::{BufWriter, Cursor};
-use web_sys::HtmlTextAreaElement;
+use braincluck_interpreter::{
+    backend, bf_parse_recovering, codegen, ir::Program, Config, SourceMap,
+};
+use std::io;
+use web_sys::{HtmlSelectElement, HtmlTextAreaElement};
 use yew::prelude::*;
 
 #[cfg(feature = "console_log")]
@@ -9,9 +11,15 @@ use log::debug;
 
 use crate::components::*;
 
+/// How many steps a program can run for before it is stopped, so infinite loops don't hang the page.
+const STEP_LIMIT: u64 = 10_000_000;
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Msg {
     Run,
+    ExportJs,
+    /// Picks the backend at this index of [`backend::backends`] to run programs with.
+    SetBackend(usize),
     ToggleExecution,
     ToggleDarkMode,
     ClearOutput,
@@ -21,6 +29,7 @@ pub enum Msg {
 pub struct App {
     dark_mode: bool,
     output_open: bool,
+    backend: usize,
     text_ref: NodeRef,
     temp_output: String,
 }
@@ -39,6 +48,7 @@ impl Component for App {
                 })
                 .unwrap_or(false),
             output_open: false,
+            backend: 0,
             text_ref: NodeRef::default(),
             temp_output: String::default(),
         }
@@ -53,25 +63,61 @@ impl Component for App {
                 if let Some(input) = self.text_ref.cast::<HtmlTextAreaElement>() {
                     let code = input.value();
 
-                    if let Ok(code) = bf_parse(&code) {
-                        let mut cells = Cells::default();
-                        let out = vec![];
-                        let mut buf_out = BufWriter::new(out);
-                        let input = vec![];
-                        let mut cursor = Cursor::new(input);
-                        if cells.interpret(&code, &mut buf_out, &mut cursor).is_ok() {
-                            let output = String::from_utf8(
-                                buf_out
-                                    .into_inner()
-                                    .expect("getting inner buffer should work"),
-                            )
-                            .expect("string should be valid utf8");
-                            self.temp_output = output;
+                    let (commands, errors) = bf_parse_recovering(&code);
+                    if errors.is_empty() {
+                        let mut out = vec![];
+                        let config = Config {
+                            step_limit: Some(STEP_LIMIT),
+                            ..Config::default()
+                        };
+                        let result = backend::backends()[self.backend].run(
+                            &commands,
+                            &mut out,
+                            &mut io::empty(),
+                            &config,
+                        );
+
+                        // Programs can write arbitrary bytes, so this can't assume valid UTF-8.
+                        let mut output = String::from_utf8_lossy(&out).into_owned();
+                        if let Err(err) = result {
+                            let err = err.with_source_map(&SourceMap::new(&code));
+                            output.push_str(&format!("\n\nError: {}", err));
                         }
+                        self.temp_output = output;
+                    } else {
+                        self.temp_output = errors
+                            .iter()
+                            .map(|err| err.render(&code))
+                            .collect::<Vec<_>>()
+                            .join("\n\n");
                     }
                 }
                 true
             }
+            Msg::ExportJs => {
+                self.output_open = true;
+
+                if let Some(input) = self.text_ref.cast::<HtmlTextAreaElement>() {
+                    let code = input.value();
+
+                    let (commands, errors) = bf_parse_recovering(&code);
+                    self.temp_output = if errors.is_empty() {
+                        codegen::js::generate(&Program::new(&commands), &Config::default())
+                            .unwrap_or_else(|err| format!("Error: {}", err))
+                    } else {
+                        errors
+                            .iter()
+                            .map(|err| err.render(&code))
+                            .collect::<Vec<_>>()
+                            .join("\n\n")
+                    };
+                }
+                true
+            }
+            Msg::SetBackend(backend) => {
+                self.backend = backend;
+                false
+            }
             Msg::ToggleExecution => {
                 self.output_open = !self.output_open;
                 true
@@ -87,10 +133,23 @@ impl Component for App {
         }
     }
 
+    // The `html!` macro expands into code that trips these lints.
+    #[allow(clippy::unnecessary_operation, clippy::let_unit_value)]
     fn view(&self, ctx: &Context<Self>) -> Html {
         // This gives us a component's "`Scope`" which allows us to send messages, etc to the component.
         let link = ctx.link();
         let run_onclick = link.callback(|_| Msg::Run);
+        let export_js_onclick = link.callback(|_| Msg::ExportJs);
+        let backend_onchange = link.callback(|e: Event| {
+            // Nothing is selected when the index is -1, which goes back to the first backend.
+            Msg::SetBackend(
+                usize::try_from(
+                    e.target_unchecked_into::<HtmlSelectElement>()
+                        .selected_index(),
+                )
+                .unwrap_or(0),
+            )
+        });
         let toggle_dark_mode_onclick = link.callback(|_| Msg::ToggleDarkMode);
         let toggle_output_onclick = link.callback(|_| Msg::ToggleExecution);
         let clear_output_onclick = link.callback(|_| Msg::ClearOutput);
@@ -113,6 +172,12 @@ impl Component for App {
                             <IconButton onclick={run_onclick} text={"Run"} class={classes!("flex-none")}>
                                 <RunIcon />
                             </IconButton>
+                            <select onchange={backend_onchange} title={"How to run the program"} class={classes!("flex-none", "rounded", "p-3", "bg-slate-200", "dark:bg-slate-600", "dark:text-slate-50")}>
+                                { for backend::backends().iter().enumerate().map(|(i, backend)| html! {
+                                    <option selected={i == self.backend} title={backend.description()}>{ backend.name() }</option>
+                                }) }
+                            </select>
+                            <IconButton onclick={export_js_onclick} text={"Export as JS"} class={classes!("flex-none")} />
                             <IconButton onclick={toggle_dark_mode_onclick} class={dark_classes}>
                                 if self.dark_mode {
                                     <SunIcon />